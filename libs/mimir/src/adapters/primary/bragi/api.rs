@@ -1,20 +1,38 @@
-use crate::utils::deserialize::deserialize_opt_duration;
+use crate::utils::deserialize::{deserialize_opt_comma_separated, deserialize_opt_duration};
 use cosmogony::ZoneType;
 use geojson::{GeoJson, Geometry};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
+use std::collections::BTreeMap;
 use std::time::Duration;
 
+use crate::adapters::primary::bragi::routes::ApiError;
 use crate::adapters::primary::common::coord::Coord;
-use crate::adapters::primary::common::filters::Filters;
+use crate::adapters::primary::common::filters::{
+    BoundingBox, Filters, PropertyFilter, StructuredAddress,
+};
+use crate::adapters::primary::common::geocoding::{Feature, GeocodeJsonResponse};
+use crate::domain::model::analysis::Token;
 use common::document::ContainerDocument;
 use places::{addr::Addr, admin::Admin, poi::Poi, stop::Stop, street::Street, PlaceDocType};
 
 pub const DEFAULT_LIMIT_RESULT_ES: i64 = 10;
 pub const DEFAULT_LIMIT_RESULT_REVERSE_API: i64 = 1;
+pub const DEFAULT_LIMIT_RESULT_ZONES: i64 = 50;
+pub const DEFAULT_NEARBY_RADIUS: u32 = 500;
 pub const DEFAULT_LANG: &str = "fr";
+/// Fields analyzed by default by the analyze endpoint: those searched by autocomplete.
+pub const DEFAULT_ANALYZED_FIELDS: [&str; 6] = [
+    "name",
+    "label",
+    "label.prefix",
+    "label.ngram",
+    "full_label.prefix",
+    "full_label.ngram",
+];
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct ForwardGeocoderExplainQuery {
     pub doc_id: String,
@@ -24,33 +42,123 @@ pub struct ForwardGeocoderExplainQuery {
     pub query: ForwardGeocoderQuery,
 }
 
+/// This structure contains all the query parameters that
+/// can be submitted for the analyze endpoint.
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AnalyzeQuery {
+    /// Text to analyze.
+    #[serde(default)]
+    pub q: String,
+    /// Type of the documents whose analyzers are used, among 'addr', 'street', 'admin',
+    /// 'poi' and 'stop'. 'admin' by default.
+    #[serde(default = "default_analyzed_doc_type")]
+    pub doc_type: String,
+    /// Fields whose analyzers are used. By default, the fields searched by autocomplete.
+    pub field: Option<Vec<String>>,
+}
+
 /// This structure contains all the query parameters that
 /// can be submitted for the autocomplete endpoint.
 ///
-/// Only the `q` parameter is mandatory.
-#[derive(Debug, Default, Serialize, Deserialize)]
+/// Either the `q` parameter, or at least one of the structured address
+/// components (`street`, `housenumber`, `postcode`, `city`, `country`) is mandatory.
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct ForwardGeocoderQuery {
+    #[serde(default)]
     pub q: String,
     pub lat: Option<f32>,
     pub lon: Option<f32>,
+    #[schemars(with = "Option<Vec<String>>")]
     pub shape_scope: Option<Vec<PlaceDocType>>,
     #[serde(default, rename = "type")]
     pub types: Option<Vec<Type>>,
     #[serde(default, rename = "zone_type")]
+    #[schemars(with = "Option<Vec<String>>")]
     pub zone_types: Option<Vec<ZoneType>>,
     pub poi_types: Option<Vec<String>>,
     #[serde(default = "default_result_limit")]
     pub limit: i64,
-    #[serde(default = "default_lang")]
-    pub lang: String,
+    /// Preferred language of the names and labels, before those of the
+    /// Accept-Language header.
+    pub lang: Option<String>,
+    /// Timeout of the query to Elasticsearch, in milliseconds.
     #[serde(deserialize_with = "deserialize_opt_duration", default)]
+    #[schemars(with = "Option<u64>")]
     pub timeout: Option<Duration>,
     pub pt_dataset: Option<Vec<String>>,
     pub poi_dataset: Option<Vec<String>>,
     pub request_id: Option<String>,
     #[serde(flatten)]
     pub proximity: Option<Proximity>,
+    pub street: Option<String>,
+    #[serde(rename = "housenumber")]
+    pub house_number: Option<String>,
+    pub postcode: Option<String>,
+    pub city: Option<String>,
+    pub country: Option<String>,
+    /// Restricts the results to a rectangle, given as "minlon,minlat,maxlon,maxlat".
+    #[serde(default)]
+    #[schemars(with = "Option<String>")]
+    pub bbox: Option<BoundingBox>,
+    /// Restricts the results to some countries, given as a comma separated list of
+    /// ISO 3166-1 alpha-2 codes.
+    #[serde(default, deserialize_with = "deserialize_opt_comma_separated")]
+    #[schemars(with = "Option<String>")]
+    pub country_codes: Option<Vec<String>>,
+    /// Restricts the results to the places located in the admin with this id.
+    pub within_admin: Option<String>,
+    /// Boosts the places located in the admin with this id.
+    pub focus_admin: Option<String>,
+    /// Restricts the POIs to those having some properties, given as
+    /// `poi_properties[key]=value`. The value is either '*' for any value,
+    /// or a comma separated list of values.
+    pub poi_properties: Option<BTreeMap<String, String>>,
+    /// Maximum number of results of some types, given as `max_per_type[poi]=2`.
+    /// It overrides the quotas of the query settings.
+    pub max_per_type: Option<BTreeMap<Type, usize>>,
+    /// Only returns the places whose confidence, in [0, 1], is at least this one.
+    pub min_confidence: Option<f64>,
+    /// Explains the score of each place found by the prefix and the fuzzy queries.
+    #[serde(default)]
+    pub debug: bool,
+    /// Format of the response. It is ignored by the batch endpoint.
+    #[serde(default)]
+    pub format: OutputFormat,
+}
+
+impl ForwardGeocoderQuery {
+    /// Returns the structured address components of the query, if any was given.
+    pub fn structured_address(&self) -> Option<StructuredAddress> {
+        let address = StructuredAddress {
+            street: self.street.clone(),
+            house_number: self.house_number.clone(),
+            postcode: self.postcode.clone(),
+            city: self.city.clone(),
+            country: self.country.clone(),
+        };
+        if address.is_empty() {
+            None
+        } else {
+            Some(address)
+        }
+    }
+
+    /// Returns the free text used to search places: the query string if there is one,
+    /// or the concatenation of the structured address components otherwise.
+    pub fn full_text(&self) -> String {
+        if !self.q.is_empty() {
+            return self.q.clone();
+        }
+        self.structured_address()
+            .map(|address| address.to_string())
+            .unwrap_or_default()
+    }
+}
+
+fn default_analyzed_doc_type() -> String {
+    Admin::static_doc_type().to_string()
 }
 
 fn default_result_limit() -> i64 {
@@ -61,13 +169,14 @@ fn default_result_limit_reverse() -> i64 {
     DEFAULT_LIMIT_RESULT_REVERSE_API
 }
 
-fn default_lang() -> String {
-    DEFAULT_LANG.to_string()
+fn default_nearby_radius() -> u32 {
+    DEFAULT_NEARBY_RADIUS
 }
 
 impl From<(ForwardGeocoderQuery, Option<Geometry>)> for Filters {
     fn from(source: (ForwardGeocoderQuery, Option<Geometry>)) -> Self {
         let (query, geometry) = source;
+        let address = query.structured_address();
         let zone_types = query
             .zone_types
             .map(|zts| zts.iter().map(|t| t.as_str().to_string()).collect());
@@ -104,36 +213,160 @@ impl From<(ForwardGeocoderQuery, Option<Geometry>)> for Filters {
             limit: query.limit,
             timeout: query.timeout,
             proximity: query.proximity,
+            address,
+            bbox: query.bbox,
+            country_codes: query.country_codes,
+            within_admin: query.within_admin,
+            focus_admin: query.focus_admin,
+            poi_properties: query.poi_properties.map(|properties| {
+                properties
+                    .into_iter()
+                    .map(|(key, value)| PropertyFilter::new(key, &value))
+                    .collect()
+            }),
         }
     }
 }
 
 /// This structure contains all the query parameters that
 /// can be submitted for the features endpoint.
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct FeaturesQuery {
     pub pt_dataset: Option<Vec<String>>,
     pub poi_dataset: Option<Vec<String>>,
+    pub lang: Option<String>,
+    #[serde(default)]
+    pub format: OutputFormat,
+    #[serde(default)]
+    pub geometry: GeometryFormat,
+    /// Tolerance of the simplification of the boundaries, in degrees.
+    pub simplify: Option<f64>,
+    /// Timeout of the query to Elasticsearch, in milliseconds.
     #[serde(deserialize_with = "deserialize_opt_duration", default)]
+    #[schemars(with = "Option<u64>")]
+    pub timeout: Option<Duration>,
+}
+
+/// This structure contains all the query parameters that
+/// can be submitted to look for several places at once on the features endpoint.
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct FeaturesBatchQuery {
+    /// Comma separated list of the ids of the places.
+    #[serde(default, deserialize_with = "deserialize_opt_comma_separated")]
+    #[schemars(with = "Option<String>")]
+    pub ids: Option<Vec<String>>,
+    pub pt_dataset: Option<Vec<String>>,
+    pub poi_dataset: Option<Vec<String>>,
+    pub lang: Option<String>,
+    #[serde(default)]
+    pub geometry: GeometryFormat,
+    /// Tolerance of the simplification of the boundaries, in degrees.
+    pub simplify: Option<f64>,
+    /// Timeout of the query to Elasticsearch, in milliseconds.
+    #[serde(deserialize_with = "deserialize_opt_duration", default)]
+    #[schemars(with = "Option<u64>")]
     pub timeout: Option<Duration>,
 }
 
 /// This structure contains all the query parameters that
 /// can be submitted for the reverse endpoint.
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct ReverseGeocoderQuery {
     pub lat: f64,
     pub lon: f64,
+    /// Search radius in meters, overrides the radius given in the query settings.
+    pub radius: Option<u32>,
+    #[serde(default, rename = "type")]
+    pub types: Option<Vec<Type>>,
+    pub poi_types: Option<Vec<String>>,
     #[serde(default = "default_result_limit_reverse")]
     pub limit: i64,
+    /// Timeout of the query to Elasticsearch, in milliseconds.
+    #[serde(deserialize_with = "deserialize_opt_duration", default)]
+    #[schemars(with = "Option<u64>")]
+    pub timeout: Option<Duration>,
+    pub pt_dataset: Option<Vec<String>>,
+    pub poi_dataset: Option<Vec<String>>,
+    pub lang: Option<String>,
+    #[serde(default)]
+    pub format: OutputFormat,
+}
+
+/// This structure contains all the query parameters that
+/// can be submitted for the zones endpoint.
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ZonesQuery {
+    pub lat: f64,
+    pub lon: f64,
+    #[serde(default, rename = "zone_type")]
+    #[schemars(with = "Option<Vec<String>>")]
+    pub zone_types: Option<Vec<ZoneType>>,
+    /// Preferred language of the names and labels, before those of the
+    /// Accept-Language header.
+    pub lang: Option<String>,
+    /// Timeout of the query to Elasticsearch, in milliseconds.
+    #[serde(deserialize_with = "deserialize_opt_duration", default)]
+    #[schemars(with = "Option<u64>")]
+    pub timeout: Option<Duration>,
+}
+
+/// This structure contains all the query parameters that
+/// can be submitted for the nearby endpoint.
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct NearbyQuery {
+    pub lat: f64,
+    pub lon: f64,
+    /// Search radius in meters, at most the maximum radius of reverse geocoding.
+    #[serde(default = "default_nearby_radius")]
+    pub radius: u32,
+    /// Types of places to look for, among 'poi' and 'public_transport:stop_area'. Both by default.
+    #[serde(default, rename = "type")]
+    pub types: Option<Vec<Type>>,
+    pub poi_types: Option<Vec<String>>,
+    #[serde(default = "default_result_limit")]
+    pub limit: i64,
+    /// Timeout of the query to Elasticsearch, in milliseconds.
     #[serde(deserialize_with = "deserialize_opt_duration", default)]
+    #[schemars(with = "Option<u64>")]
     pub timeout: Option<Duration>,
+    pub pt_dataset: Option<Vec<String>>,
+    pub poi_dataset: Option<Vec<String>>,
+    pub lang: Option<String>,
+    #[serde(default)]
+    pub format: OutputFormat,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// This structure contains all the query parameters that
+/// can be submitted for the codes endpoint.
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct CodesQuery {
+    /// Types of places to look for, among 'public_transport:stop_area', 'zone' and 'city'. All of
+    /// them by default.
+    #[serde(default, rename = "type")]
+    pub types: Option<Vec<Type>>,
+    pub pt_dataset: Option<Vec<String>>,
+    #[serde(default = "default_result_limit")]
+    pub limit: i64,
+    pub lang: Option<String>,
+    #[serde(default)]
+    pub format: OutputFormat,
+    /// Timeout of the query to Elasticsearch, in milliseconds.
+    #[serde(deserialize_with = "deserialize_opt_duration", default)]
+    #[schemars(with = "Option<u64>")]
+    pub timeout: Option<Duration>,
+}
+
+/// The body of POST requests, restricting the results to a shape.
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct JsonParam {
+    /// A GeoJSON geometry.
+    #[schemars(with = "JsonValue")]
     pub shape: GeoJson,
 }
 
@@ -149,19 +382,64 @@ impl From<JsonValue> for ExplainResponseBody {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// The tokens obtained by analyzing a text, for each analyzed field.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct AnalyzeResponseBody {
+    pub fields: BTreeMap<String, Vec<Token>>,
+}
+
+/// The response to a batch of forward geocoding queries.
+///
+/// There is exactly one result per submitted query, in the order of submission.
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct BatchResponseBody {
+    pub results: Vec<BatchItemResponse>,
+}
+
+/// The response to a lookup of several places by id.
+///
+/// There is exactly one result per requested id, in the order of the request.
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct FeaturesResponseBody {
+    pub results: Vec<FeatureItemResponse>,
+}
+
+/// The result for a single id: either the place with this id, as a geocodejson
+/// feature, or the error explaining why it could not be found.
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum FeatureItemResponse {
+    Feature(Feature),
+    Error { id: String, error: ApiError },
+}
+
+/// The result of a single query in a batch: either a geocodejson response,
+/// or the error that prevented this query from being answered.
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum BatchItemResponse {
+    Response(GeocodeJsonResponse),
+    Error { error: ApiError },
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct BragiStatus {
     pub version: String,
+    /// Hash of the query settings in use, which changes when they are reloaded.
+    pub query_settings_hash: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct MimirStatus {
     pub version: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct ElasticsearchStatus {
     pub version: String,
@@ -169,7 +447,7 @@ pub struct ElasticsearchStatus {
     pub url: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct StatusResponseBody {
     pub bragi: BragiStatus,
@@ -178,19 +456,21 @@ pub struct StatusResponseBody {
 }
 
 /// This macro is used to define the forward_geocoder route.
-/// It takes a client (ElasticsearchStorage) and query settings
+/// It takes a client (ElasticsearchStorage), a handle on the query settings, a timeout,
+/// and the languages used when those of the request are not available.
 /// It can be either a GET request, with query parameters,
 /// or a POST request, with both query parameters and a GeoJson shape
 /// in the body.
 #[macro_export]
 macro_rules! forward_geocoder {
-    ($cl:expr, $st:expr, $ti:expr) => {
+    ($cl:expr, $st:expr, $ti:expr, $lf:expr) => {
         routes::forward_geocoder_get()
             .or(routes::forward_geocoder_post())
             .unify()
             .and(routes::with_client($cl))
-            .and(routes::with_settings($st))
+            .and(routes::with_profile($st))
             .and(routes::with_timeout($ti))
+            .and(routes::with_langs($lf))
             .and_then(handlers::forward_geocoder)
     };
 }
@@ -199,52 +479,160 @@ pub use forward_geocoder;
 
 #[macro_export]
 macro_rules! forward_geocoder_explain {
-    ($cl:expr, $st:expr, $ti:expr) => {
+    ($cl:expr, $st:expr, $ti:expr, $lf:expr) => {
         routes::forward_geocoder_explain_get()
             .or(routes::forward_geocoder_explain_post())
             .unify()
             .and(routes::with_client($cl))
-            .and(routes::with_settings($st))
+            .and(routes::with_profile($st))
             .and(routes::with_timeout($ti))
+            .and(routes::with_langs($lf))
             .and_then(handlers::forward_geocoder_explain)
     };
 }
 pub use forward_geocoder_explain;
 
+/// This macro is used to define the route analyzing a text as Elasticsearch does.
+/// It takes a client (ElasticsearchStorage), and whether the route is enabled.
+#[macro_export]
+macro_rules! analyze {
+    ($cl:expr, $en:expr) => {
+        routes::analyze($en)
+            .and(routes::with_client($cl))
+            .and_then(handlers::analyze)
+    };
+}
+pub use analyze;
+
+/// This macro is used to define the batch forward_geocoder route.
+/// It takes a client, a handle on the query settings, a timeout for each query,
+/// the maximum number of queries in a batch, the number of queries run concurrently,
+/// and the fallback languages.
+#[macro_export]
+macro_rules! forward_geocoder_batch {
+    ($cl:expr, $st:expr, $ti:expr, $mx:expr, $co:expr, $lf:expr) => {
+        routes::forward_geocoder_batch($mx)
+            .and(routes::with_client($cl))
+            .and(routes::with_profile($st))
+            .and(routes::with_timeout($ti))
+            .and(routes::with_concurrency($co))
+            .and(routes::with_langs($lf))
+            .and_then(handlers::forward_geocoder_batch)
+    };
+}
+pub use forward_geocoder_batch;
+
 #[macro_export]
 macro_rules! reverse_geocoder {
-    ($cl:expr, $st:expr, $ti:expr) => {
-        routes::reverse_geocoder()
+    ($cl:expr, $st:expr, $ti:expr, $mx:expr, $lf:expr) => {
+        routes::reverse_geocoder($mx)
             .and(routes::with_client($cl))
-            .and(routes::with_settings($st))
+            .and(routes::with_profile($st))
             .and(routes::with_timeout($ti))
+            .and(routes::with_langs($lf))
             .and_then(handlers::reverse_geocoder)
     };
 }
 pub use reverse_geocoder;
 
+#[macro_export]
+macro_rules! zones {
+    ($cl:expr, $ti:expr, $lf:expr) => {
+        routes::zones()
+            .and(routes::with_client($cl))
+            .and(routes::with_timeout($ti))
+            .and(routes::with_langs($lf))
+            .and_then(handlers::zones)
+    };
+}
+pub use zones;
+
+#[macro_export]
+macro_rules! nearby {
+    ($cl:expr, $ti:expr, $mx:expr, $ml:expr, $lf:expr) => {
+        routes::nearby($mx, $ml)
+            .and(routes::with_client($cl))
+            .and(routes::with_timeout($ti))
+            .and(routes::with_langs($lf))
+            .and_then(handlers::nearby)
+    };
+}
+pub use nearby;
+
+#[macro_export]
+macro_rules! codes {
+    ($cl:expr, $ti:expr, $lf:expr) => {
+        routes::codes()
+            .and(routes::with_client($cl))
+            .and(routes::with_timeout($ti))
+            .and(routes::with_langs($lf))
+            .and_then(handlers::codes)
+    };
+}
+pub use codes;
+
 #[macro_export]
 macro_rules! features {
-    ($cl:expr, $ti:expr) => {
+    ($cl:expr, $ti:expr, $lf:expr) => {
         routes::features()
             .and(routes::with_client($cl))
             .and(routes::with_timeout($ti))
+            .and(routes::with_langs($lf))
             .and_then(handlers::features)
     };
 }
 pub use features;
 
+/// This macro is used to define the route looking for several places by id.
+/// It takes a client, a timeout, the maximum number of ids in a request,
+/// and the fallback languages.
+#[macro_export]
+macro_rules! features_batch {
+    ($cl:expr, $ti:expr, $mx:expr, $lf:expr) => {
+        routes::features_batch($mx)
+            .and(routes::with_client($cl))
+            .and(routes::with_timeout($ti))
+            .and(routes::with_langs($lf))
+            .and_then(handlers::features_batch)
+    };
+}
+pub use features_batch;
+
+/// This macro is used to define the GraphQL route.
+/// It takes a client, a handle on the query settings, from which the profile of each request
+/// is selected, the timeouts and the limits of each kind of query, and the fallback languages.
+#[macro_export]
+macro_rules! graphql {
+    ($cl:expr, $st:expr, $ti:expr, $li:expr, $lf:expr) => {
+        routes::graphql(gql::schema($cl, $st.clone(), $ti, $li))
+            .and(routes::with_profile($st))
+            .and(routes::with_langs($lf))
+            .and_then(handlers::graphql)
+    };
+}
+pub use graphql;
+
 #[macro_export]
 macro_rules! status {
-    ($cl:expr, $es:expr) => {
+    ($cl:expr, $es:expr, $st:expr) => {
         routes::status()
             .and(routes::with_client($cl))
             .and(routes::with_elasticsearch($es))
+            .and(routes::with_settings_handle($st))
             .and_then(handlers::status)
     };
 }
 pub use status;
 
+/// This macro is used to define the route serving the OpenAPI document of the REST API.
+#[macro_export]
+macro_rules! openapi {
+    () => {
+        routes::openapi().and_then(handlers::openapi)
+    };
+}
+pub use openapi;
+
 #[macro_export]
 macro_rules! metrics {
     () => {
@@ -253,7 +641,9 @@ macro_rules! metrics {
 }
 pub use metrics;
 
-#[derive(PartialEq, Copy, Clone, Debug, Deserialize, Serialize)]
+#[derive(
+    PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug, Deserialize, Serialize, JsonSchema,
+)]
 pub enum Type {
     #[serde(rename = "house")]
     House,
@@ -268,6 +658,9 @@ pub enum Type {
     // TODO To be deleted when switching to full ES7 (in production)
     #[serde(rename = "city")]
     City,
+    /// Coordinates typed in the query. They are not indexed, so they can't be requested.
+    #[serde(rename = "coord", skip_deserializing)]
+    Coord,
 }
 
 impl Type {
@@ -279,29 +672,79 @@ impl Type {
             Type::Street => "street",
             Type::Zone => "zone",
             Type::City => "city",
+            Type::Coord => "coord",
         }
     }
 
-    pub fn as_index_type(&self) -> &'static str {
+    /// Returns the type of the documents of this type, if they are indexed.
+    pub fn as_index_type(&self) -> Option<&'static str> {
         match self {
-            Type::House => Addr::static_doc_type(),
-            Type::Poi => Poi::static_doc_type(),
-            Type::StopArea => Stop::static_doc_type(),
-            Type::Street => Street::static_doc_type(),
-            Type::Zone | Type::City => Admin::static_doc_type(),
+            Type::House => Some(Addr::static_doc_type()),
+            Type::Poi => Some(Poi::static_doc_type()),
+            Type::StopArea => Some(Stop::static_doc_type()),
+            Type::Street => Some(Street::static_doc_type()),
+            Type::Zone | Type::City => Some(Admin::static_doc_type()),
+            Type::Coord => None,
         }
     }
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+/// The formats in which places can be returned.
+#[derive(PartialEq, Copy, Clone, Debug, Deserialize, Serialize, JsonSchema)]
+pub enum OutputFormat {
+    /// GeocodeJSON, the default format.
+    #[serde(rename = "geocodejson")]
+    GeocodeJson,
+    /// The `json` format of Nominatim.
+    #[serde(rename = "nominatim")]
+    Nominatim,
+    /// The `jsonv2` format of Nominatim.
+    #[serde(rename = "nominatim_v2")]
+    NominatimV2,
+    /// The GeoJSON responses of Pelias.
+    #[serde(rename = "pelias")]
+    Pelias,
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        OutputFormat::GeocodeJson
+    }
+}
+
+/// The geometries with which places can be returned.
+///
+/// Only admins have a bounding box and a boundary, the other places are
+/// always returned with their coordinates.
+#[derive(PartialEq, Copy, Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GeometryFormat {
+    /// The coordinates of the place, the default.
+    Point,
+    /// The bounding box of admins, as a polygon.
+    Bbox,
+    /// The boundary of admins.
+    Full,
+}
+
+impl Default for GeometryFormat {
+    fn default() -> Self {
+        GeometryFormat::Point
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
 pub struct Proximity {
     #[serde(with = "serde_with::rust::display_fromstr")]
     #[serde(rename = "proximity_scale")]
+    #[schemars(with = "f64")]
     pub scale: f64,
     #[serde(with = "serde_with::rust::display_fromstr")]
     #[serde(rename = "proximity_offset")]
+    #[schemars(with = "f64")]
     pub offset: f64,
     #[serde(with = "serde_with::rust::display_fromstr")]
     #[serde(rename = "proximity_decay")]
+    #[schemars(with = "f64")]
     pub decay: f64,
 }
@@ -1,317 +1,698 @@
-use async_graphql::*;
-use common::document::ContainerDocument;
-use places::{addr::Addr, admin::Admin, poi::Poi, stop::Stop, street::Street};
-use serde::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
-use tokio::io::AsyncReadExt;
-
-use crate::adapters::primary::bragi::autocomplete::{build_query, Coord, Filters};
-use crate::adapters::primary::bragi::settings::QuerySettings;
+use async_graphql::{
+    Context, EmptyMutation, EmptySubscription, Enum, ErrorExtensions, InputObject, Json, Object,
+    Schema, SchemaBuilder, SimpleObject, Union,
+};
+use geojson::Geometry;
+use serde_json::{json, Value as JsonValue};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::adapters::primary::bragi::api::{
+    FeaturesQuery, ForwardGeocoderExplainQuery, ReverseGeocoderQuery, Type,
+    DEFAULT_LIMIT_RESULT_ES, DEFAULT_LIMIT_RESULT_REVERSE_API,
+};
+use crate::adapters::primary::bragi::handlers::{self, InternalError};
+use crate::adapters::primary::bragi::routes::{
+    validate_forward_geocoder_query, validate_reverse_geocoder_params, ApiError,
+};
+use crate::adapters::primary::common::geocoding::{localized, FromWithLang};
+use crate::adapters::primary::common::settings::{Profile, QuerySettings, SettingsHandle};
 use crate::adapters::secondary::elasticsearch::ElasticsearchStorage;
-use crate::domain::model::query::Query as SearchQuery;
-use crate::domain::ports::primary::explain_query::ExplainDocument;
-use crate::domain::ports::primary::search_documents::SearchDocuments;
-
-// impl ErrorExtensions for SearchError {
-//     // lets define our base extensions
-//     fn extend(&self) -> FieldError {
-//         self.extend_with(|err, e| match err {
-//             &SearchError::DocumentRetrievalError { source } => e.set("reason", source.to_string()),
-//             &SearchError::InterfaceError { details } => e.set("reason", details.to_string()),
-//         })
-//     }
-// }
-
-/*
- * The following is an attempt at turning coord into an async_graphql input type,
- * but, in the interest of time, i ll complete this later
-#[derive(Debug, Serialize, Deserialize)]
-struct InputCoord(Coord);
-
-impl Display for InputCoord {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "(lat: {}, lon: {})", self.0.lat(), self.0.lon())
-    }
+
+pub type BragiSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+/// Maximum nesting of the selections of a query, enough for the introspection query of
+/// GraphQL clients.
+const MAX_DEPTH: usize = 16;
+
+/// Complexity of a field searching Elasticsearch, added to the complexity of its selection.
+const SEARCH_COMPLEXITY: usize = 100;
+
+/// Maximum complexity of a query. As each search costs `SEARCH_COMPLEXITY`, this bounds the
+/// number of searches of a single request, aliased fields included.
+const MAX_COMPLEXITY: usize = 10 * SEARCH_COMPLEXITY;
+
+/// Timeouts used for the requests to Elasticsearch, for each kind of query.
+#[derive(Debug, Clone, Copy)]
+pub struct Timeouts {
+    pub autocomplete: Duration,
+    pub reverse: Duration,
+    pub features: Duration,
 }
 
-impl Type for InputCoord {
-}
-impl InputType for InputCoord {
-    fn parse(value: GraphqlValue) -> InputValueResult<Self> {
-        match &value {
-            GraphqlValue::Object(o) => {
-                let lat = o.get("lat").ok_or(InputValueError::custom("missing lat"))?;
-                let lat = lat
-                    .into_json()
-                    .unwrap()
-                    .as_f64()
-                    .ok_or(InputValueError::custom("lat is not f64"))?;
-                let lon: f64 = o.get("lon").ok_or(InputValueError::custom("missing lon"));
-                let lon = lon
-                    .into_json()
-                    .unwrap()
-                    .as_f64()
-                    .ok_or(InputValueError::custom("lat is not f64"))?;
-                Ok(InputCoord(Coord::new(lon, lat)))
-            }
-            _ => Err(InputValueError::expected_type(value)),
-        }
+/// Limits on the parameters of the queries, the same as those of the REST API.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub reverse_max_radius: u32,
+}
+
+/// Builds the GraphQL schema of bragi.
+///
+/// The schema exposes the same queries as the REST API, but returns typed places,
+/// so that clients can select the fields they need for each type of place.
+pub fn schema(
+    client: ElasticsearchStorage,
+    settings: SettingsHandle,
+    timeouts: Timeouts,
+    limits: Limits,
+) -> BragiSchema {
+    schema_builder()
+        .data(client)
+        .data(settings)
+        .data(timeouts)
+        .data(limits)
+        .finish()
+}
+
+// The schema, with the limits on the queries, but without the data used by the queries.
+fn schema_builder() -> SchemaBuilder<Query, EmptyMutation, EmptySubscription> {
+    Schema::build(Query, EmptyMutation, EmptySubscription)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+}
+
+/// The languages of a GraphQL request, taken from its Accept-Language header
+/// and the fallback languages. They are attached to each request as context data.
+#[derive(Debug, Clone, Default)]
+pub struct Langs(pub Vec<String>);
+
+// Returns the languages of a query: its `lang` argument, if any, followed by
+// the languages of the request.
+fn query_langs(ctx: &Context<'_>, lang: Option<String>) -> Vec<String> {
+    let langs = ctx
+        .data_opt::<Langs>()
+        .map(|langs| langs.0.clone())
+        .unwrap_or_default();
+    handlers::resolve_langs(lang, langs)
+}
+
+// Returns the query settings of the profile selected by the request, which is attached
+// to it as context data, or the default query settings.
+fn query_settings(ctx: &Context<'_>) -> async_graphql::Result<Arc<QuerySettings>> {
+    match ctx.data_opt::<Profile>() {
+        Some(profile) => Ok(profile.settings.clone()),
+        None => Ok(ctx.data::<SettingsHandle>()?.current()),
     }
+}
 
-    fn to_value(&self) -> GraphqlValue {
-        let v = serde_json::to_value(self.0).expect("no pb").clone();
-        GraphqlValue::from(v)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum PlaceType {
+    House,
+    Poi,
+    StopArea,
+    Street,
+    Zone,
+}
+
+impl From<PlaceType> for Type {
+    fn from(place_type: PlaceType) -> Self {
+        match place_type {
+            PlaceType::House => Type::House,
+            PlaceType::Poi => Type::Poi,
+            PlaceType::StopArea => Type::StopArea,
+            PlaceType::Street => Type::Street,
+            PlaceType::Zone => Type::Zone,
+        }
     }
 }
-*/
 
-#[derive(Debug, Serialize, Deserialize, InputObject)]
-#[serde(rename_all = "snake_case")]
-struct InputFilters {
+/// Filters of a forward geocoding query. They have the same meaning as the
+/// query parameters of the autocomplete endpoint.
+#[derive(Debug, InputObject)]
+pub struct InputFilters {
     lat: Option<f32>,
     lon: Option<f32>,
-    shape: Option<String>,
-    shape_scope: Option<Vec<String>>, // Here I merge shape and shape_scope together, (and I use str)
-    datasets: Option<Vec<String>>,
+    shape: Option<Json<Geometry>>,
+    types: Option<Vec<PlaceType>>,
     zone_types: Option<Vec<String>>,
     poi_types: Option<Vec<String>>,
+    pt_dataset: Option<Vec<String>>,
+    poi_dataset: Option<Vec<String>>,
 }
 
-impl From<InputFilters> for Filters {
-    fn from(input: InputFilters) -> Self {
-        Filters {
-            // When option_zip_option becomes available: coord: input.lat.zip_with(input.lon, Coord::new),
-            coord: match (input.lat, input.lon) {
-                (Some(lat), Some(lon)) => Some(Coord::new(lat, lon)),
-                _ => None,
-            },
-            shape: match (input.shape, input.shape_scope) {
-                (Some(shape), Some(shape_scope)) => Some((shape, shape_scope)),
-                _ => None,
-            },
-            datasets: input.datasets,
-            zone_types: input.zone_types,
-            poi_types: input.poi_types,
-        }
+impl InputFilters {
+    // The filters are turned into the JSON representation of a `ForwardGeocoderQuery`,
+    // so that they go through the same validation as the REST queries.
+    fn to_json(&self) -> JsonValue {
+        json!({
+            "lat": self.lat,
+            "lon": self.lon,
+            "type": self.types.as_ref().map(|types| {
+                types
+                    .iter()
+                    .map(|t| Type::from(*t).as_str())
+                    .collect::<Vec<_>>()
+            }),
+            "zone_type": self.zone_types,
+            "poi_types": self.poi_types,
+            "pt_dataset": self.pt_dataset,
+            "poi_dataset": self.poi_dataset,
+        })
     }
 }
-// I'm about here.... and it's late
-// I need to identify the output type, and put it there instead of Index
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
-struct SearchResponseBody {
-    pub docs: Vec<JsonValue>,
-    pub docs_count: usize,
+fn forward_geocoder_json(
+    q: String,
+    filters: &Option<InputFilters>,
+    limit: Option<i64>,
+    lang: &Option<String>,
+) -> JsonValue {
+    let mut query = filters
+        .as_ref()
+        .map(InputFilters::to_json)
+        .unwrap_or_else(|| json!({}));
+    query["q"] = json!(q);
+    query["limit"] = json!(limit.unwrap_or(DEFAULT_LIMIT_RESULT_ES));
+    if let Some(lang) = lang {
+        query["lang"] = json!(lang);
+    }
+    query
 }
 
+pub struct Query;
+
 #[Object]
-impl SearchResponseBody {
-    async fn docs(&self) -> &Vec<JsonValue> {
-        &self.docs
+impl Query {
+    /// Searches the places matching the query string `q`. The near-identical places are
+    /// collapsed as in the REST API.
+    #[graphql(complexity = "SEARCH_COMPLEXITY + child_complexity")]
+    async fn autocomplete(
+        &self,
+        ctx: &Context<'_>,
+        q: String,
+        filters: Option<InputFilters>,
+        limit: Option<i64>,
+        lang: Option<String>,
+    ) -> async_graphql::Result<Vec<AutocompleteResult>> {
+        let geometry = filters
+            .as_ref()
+            .and_then(|filters| filters.shape.as_ref())
+            .map(|shape| shape.0.clone());
+        let params =
+            validate_forward_geocoder_query(forward_geocoder_json(q, &filters, limit, &lang))
+                .await
+                .map_err(invalid_request)?;
+        let client = ctx.data::<ElasticsearchStorage>()?;
+        let settings = query_settings(ctx)?;
+        let timeout = ctx.data::<Timeouts>()?.autocomplete;
+        let langs = query_langs(ctx, lang);
+        let places =
+            handlers::forward_geocode_places(client, params, geometry, &settings, timeout, &langs)
+                .await
+                .map_err(internal_error)?;
+        Ok(places
+            .into_iter()
+            .map(|scored| AutocompleteResult {
+                place: Place::from_with_lang(scored.place, &langs),
+                confidence: scored.confidence,
+                collapsed_ids: scored.collapsed_ids,
+            })
+            .collect())
     }
 
-    async fn docs_count(&self) -> &usize {
-        &self.docs_count
+    /// Searches the places around the location given by `lat` and `lon`.
+    #[allow(clippy::too_many_arguments)]
+    #[graphql(complexity = "SEARCH_COMPLEXITY + child_complexity")]
+    async fn reverse(
+        &self,
+        ctx: &Context<'_>,
+        lat: f64,
+        lon: f64,
+        radius: Option<u32>,
+        types: Option<Vec<PlaceType>>,
+        poi_types: Option<Vec<String>>,
+        pt_dataset: Option<Vec<String>>,
+        poi_dataset: Option<Vec<String>>,
+        limit: Option<i64>,
+        lang: Option<String>,
+    ) -> async_graphql::Result<Vec<Place>> {
+        let params = ReverseGeocoderQuery {
+            lat,
+            lon,
+            radius,
+            types: types.map(|types| types.into_iter().map(Type::from).collect()),
+            poi_types,
+            limit: limit.unwrap_or(DEFAULT_LIMIT_RESULT_REVERSE_API),
+            pt_dataset,
+            poi_dataset,
+            ..Default::default()
+        };
+        let max_radius = ctx.data::<Limits>()?.reverse_max_radius;
+        let params = validate_reverse_geocoder_params(params, max_radius)
+            .await
+            .map_err(invalid_request)?;
+        let client = ctx.data::<ElasticsearchStorage>()?;
+        let settings = query_settings(ctx)?;
+        let timeout = ctx.data::<Timeouts>()?.reverse;
+        let langs = query_langs(ctx, lang);
+        let places = handlers::reverse_geocode_places(client, params, &settings, timeout)
+            .await
+            .map_err(internal_error)?;
+        Ok(places
+            .into_iter()
+            .map(|place| Place::from_with_lang(place, &langs))
+            .collect())
     }
+
+    /// Retrieves the places with the given `id`.
+    #[graphql(complexity = "SEARCH_COMPLEXITY + child_complexity")]
+    async fn features(
+        &self,
+        ctx: &Context<'_>,
+        id: String,
+        pt_dataset: Option<Vec<String>>,
+        poi_dataset: Option<Vec<String>>,
+        lang: Option<String>,
+    ) -> async_graphql::Result<Vec<Place>> {
+        let params = FeaturesQuery {
+            pt_dataset,
+            poi_dataset,
+            ..Default::default()
+        };
+        let client = ctx.data::<ElasticsearchStorage>()?;
+        let timeout = ctx.data::<Timeouts>()?.features;
+        let langs = query_langs(ctx, lang);
+        let places = handlers::feature_places(client, &[id], params, timeout)
+            .await
+            .map_err(internal_error)?;
+        Ok(places
+            .into_iter()
+            .map(|place| Place::from_with_lang(place, &langs))
+            .collect())
+    }
+
+    /// Explains how the document `id` of type `docType` is scored by the query `q`.
+    #[graphql(complexity = "SEARCH_COMPLEXITY + child_complexity")]
+    async fn explain(
+        &self,
+        ctx: &Context<'_>,
+        q: String,
+        id: String,
+        doc_type: String,
+        filters: Option<InputFilters>,
+        lang: Option<String>,
+    ) -> async_graphql::Result<Json<JsonValue>> {
+        let geometry = filters
+            .as_ref()
+            .and_then(|filters| filters.shape.as_ref())
+            .map(|shape| shape.0.clone());
+        let mut query = forward_geocoder_json(q, &filters, None, &lang);
+        query["doc_id"] = json!(id);
+        query["doc_type"] = json!(doc_type);
+        let params: ForwardGeocoderExplainQuery = serde_json::from_value(query).map_err(|err| {
+            invalid_request(ApiError {
+                short: "validation error".to_string(),
+                long: err.to_string(),
+            })
+        })?;
+        let client = ctx.data::<ElasticsearchStorage>()?;
+        let settings = query_settings(ctx)?;
+        let langs = query_langs(ctx, lang);
+        let explanation =
+            handlers::explain_forward_geocode(client, params, geometry, &settings, &langs)
+                .await
+                .map_err(internal_error)?;
+        Ok(Json(explanation))
+    }
+}
+
+fn internal_error(err: InternalError) -> async_graphql::Error {
+    let reason = format!("{:?}", err.reason);
+    async_graphql::Error::new(err.info).extend_with(move |_, e| e.set("reason", reason))
+}
+
+fn invalid_request(err: ApiError) -> async_graphql::Error {
+    let ApiError { short, long } = err;
+    async_graphql::Error::new(long).extend_with(move |_, e| e.set("reason", short))
 }
 
-impl From<Vec<JsonValue>> for SearchResponseBody {
-    fn from(values: Vec<JsonValue>) -> Self {
-        SearchResponseBody {
-            docs_count: values.len(),
-            docs: values,
+/// A place found by the autocomplete query.
+#[derive(Debug, SimpleObject)]
+pub struct AutocompleteResult {
+    pub place: Place,
+    /// Confidence, in [0, 1], that the place is the one the query is looking for.
+    pub confidence: f64,
+    /// Ids of the near-identical places collapsed into this one.
+    pub collapsed_ids: Vec<String>,
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Union)]
+pub enum Place {
+    Admin(Admin),
+    Street(Street),
+    Addr(Addr),
+    Poi(Poi),
+    Stop(Stop),
+}
+
+impl FromWithLang<places::Place> for Place {
+    fn from_with_lang(place: places::Place, langs: &[String]) -> Self {
+        match place {
+            places::Place::Admin(admin) => Place::Admin(Admin::from_with_lang(admin, langs)),
+            places::Place::Street(street) => Place::Street(Street::from_with_lang(street, langs)),
+            places::Place::Addr(addr) => Place::Addr(Addr::from_with_lang(addr, langs)),
+            places::Place::Poi(poi) => Place::Poi(Poi::from_with_lang(poi, langs)),
+            places::Place::Stop(stop) => Place::Stop(Stop::from_with_lang(stop, langs)),
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
-struct ExplainResponseBody {
-    pub explanation: JsonValue,
+/// The address of a POI, which is either a precise address, or just a street.
+#[derive(Debug, Union)]
+pub enum Address {
+    Street(Street),
+    Addr(Addr),
 }
 
-#[Object]
-impl ExplainResponseBody {
-    async fn explanation(&self) -> &JsonValue {
-        &self.explanation
+#[derive(Debug, SimpleObject)]
+pub struct Coord {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl From<places::coord::Coord> for Coord {
+    fn from(coord: places::coord::Coord) -> Self {
+        Coord {
+            lat: coord.lat(),
+            lon: coord.lon(),
+        }
     }
 }
 
-impl From<JsonValue> for ExplainResponseBody {
-    fn from(explanation: JsonValue) -> Self {
-        ExplainResponseBody { explanation }
+#[derive(Debug, SimpleObject)]
+pub struct KeyValue {
+    pub key: String,
+    pub value: String,
+}
+
+fn key_values<'a>(map: impl IntoIterator<Item = (&'a String, &'a String)>) -> Vec<KeyValue> {
+    map.into_iter()
+        .map(|(key, value)| KeyValue {
+            key: key.clone(),
+            value: value.clone(),
+        })
+        .collect()
+}
+
+/// An administrative region containing a place.
+#[derive(Debug, SimpleObject)]
+pub struct AdminRegion {
+    pub id: String,
+    pub name: String,
+    pub label: String,
+    pub coord: Coord,
+    pub zone_type: Option<String>,
+    pub level: u32,
+    pub insee: String,
+    pub zip_codes: Vec<String>,
+}
+
+impl FromWithLang<&places::admin::Admin> for AdminRegion {
+    fn from_with_lang(admin: &places::admin::Admin, langs: &[String]) -> Self {
+        let name = localized(&admin.names, langs, &admin.name);
+        let label = localized(&admin.labels, langs, &admin.label);
+        AdminRegion {
+            id: admin.id.clone(),
+            name: name.to_string(),
+            label: label.to_string(),
+            coord: admin.coord.into(),
+            zone_type: admin.zone_type.map(|zt| zt.as_str().to_string()),
+            level: admin.level,
+            insee: admin.insee.clone(),
+            zip_codes: admin.zip_codes.clone(),
+        }
     }
 }
 
-pub struct Query;
+fn admin_regions(
+    admins: &[std::sync::Arc<places::admin::Admin>],
+    langs: &[String],
+) -> Vec<AdminRegion> {
+    admins
+        .iter()
+        .map(|admin| AdminRegion::from_with_lang(admin.as_ref(), langs))
+        .collect()
+}
 
-#[Object]
-impl Query {
-    // FIXME We need a query, even if it does nothing
-    async fn no_op(&self, _context: &Context<'_>) -> FieldResult<Option<i32>> {
-        Ok(None)
+#[derive(Debug, SimpleObject)]
+pub struct Admin {
+    pub id: String,
+    pub name: String,
+    pub label: String,
+    pub coord: Coord,
+    /// Distance, in meters, to the location given in the query.
+    pub distance: Option<u32>,
+    pub zone_type: Option<String>,
+    pub level: u32,
+    pub insee: String,
+    pub zip_codes: Vec<String>,
+    pub country_codes: Vec<String>,
+    pub parent_id: Option<String>,
+    pub codes: Vec<KeyValue>,
+    pub administrative_regions: Vec<AdminRegion>,
+}
+
+impl FromWithLang<places::admin::Admin> for Admin {
+    fn from_with_lang(admin: places::admin::Admin, langs: &[String]) -> Self {
+        let region = AdminRegion::from_with_lang(&admin, langs);
+        Admin {
+            id: region.id,
+            name: region.name,
+            label: region.label,
+            coord: region.coord,
+            distance: admin.distance,
+            zone_type: region.zone_type,
+            level: region.level,
+            insee: region.insee,
+            zip_codes: region.zip_codes,
+            country_codes: admin.country_codes,
+            parent_id: admin.parent_id,
+            codes: key_values(&admin.codes),
+            administrative_regions: admin_regions(&admin.administrative_regions, langs),
+        }
     }
 }
 
-// Note that we have to use a mutation for the forward_geocoder, even though it does not
-// mutate the server. This is because we upload the settings, and the type Upload requires
-// a mutation.
-pub struct Mutation;
+#[derive(Debug, SimpleObject)]
+pub struct Street {
+    pub id: String,
+    pub name: String,
+    pub label: String,
+    pub coord: Coord,
+    /// Distance, in meters, to the location given in the query.
+    pub distance: Option<u32>,
+    pub zip_codes: Vec<String>,
+    pub country_codes: Vec<String>,
+    pub administrative_regions: Vec<AdminRegion>,
+}
 
-#[Object]
-impl Mutation {
-    async fn forward_geocoder(
-        &self,
-        context: &Context<'_>,
-        q: String,
-        filters: InputFilters,
-        settings: Upload,
-    ) -> FieldResult<SearchResponseBody> {
-        let client = get_client_from_context(context)?;
-
-        // Read settings from uploaded file
-        let settings = settings
-            .value(context)
-            .map_err(|err| to_err("extract settings from upload", "graphql", err.to_string()))?;
-
-        // FIXME Use the new QuerySettings::new_from_file function
-        let mut settings_content = String::new();
-        let mut settings_file = tokio::fs::File::from_std(settings.content);
-        settings_file
-            .read_to_string(&mut settings_content)
-            .await
-            .map_err(|err| to_err("read settings from content", "graphql", err.to_string()))?;
-        let settings = QuerySettings::new(&settings_content)
-            .map_err(|err| to_err("invalid settings", "graphql", err.to_string()))?;
+impl FromWithLang<places::street::Street> for Street {
+    fn from_with_lang(street: places::street::Street, langs: &[String]) -> Self {
+        Street {
+            administrative_regions: admin_regions(&street.administrative_regions, langs),
+            id: street.id,
+            name: street.name,
+            label: street.label,
+            coord: street.coord.into(),
+            distance: street.distance,
+            zip_codes: street.zip_codes,
+            country_codes: street.country_codes,
+        }
+    }
+}
 
-        let filters = Filters::from(filters);
-        let dsl = build_query(&q, filters, &["fr"], &settings);
+#[derive(Debug, SimpleObject)]
+pub struct Addr {
+    pub id: String,
+    pub name: String,
+    pub label: String,
+    pub coord: Coord,
+    /// Distance, in meters, to the location given in the query.
+    pub distance: Option<u32>,
+    pub house_number: String,
+    pub street: Street,
+    pub zip_codes: Vec<String>,
+    pub country_codes: Vec<String>,
+    pub administrative_regions: Vec<AdminRegion>,
+}
 
-        let res = client
-            .search_documents(
-                vec![configuration::root()],
-                SearchQuery::QueryDSL(dsl),
-            )
-            .await?;
+impl FromWithLang<places::addr::Addr> for Addr {
+    fn from_with_lang(addr: places::addr::Addr, langs: &[String]) -> Self {
+        Addr {
+            administrative_regions: admin_regions(&addr.street.administrative_regions, langs),
+            id: addr.id,
+            name: addr.name,
+            label: addr.label,
+            coord: addr.coord.into(),
+            distance: addr.distance,
+            house_number: addr.house_number,
+            street: Street::from_with_lang(addr.street, langs),
+            zip_codes: addr.zip_codes,
+            country_codes: addr.country_codes,
+        }
+    }
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct PoiType {
+    pub id: String,
+    pub name: String,
+}
 
-        Ok(res.into())
+#[derive(Debug, SimpleObject)]
+pub struct Poi {
+    pub id: String,
+    pub name: String,
+    pub label: String,
+    pub coord: Coord,
+    /// Distance, in meters, to the location given in the query.
+    pub distance: Option<u32>,
+    pub poi_type: PoiType,
+    pub properties: Vec<KeyValue>,
+    pub address: Option<Address>,
+    pub zip_codes: Vec<String>,
+    pub country_codes: Vec<String>,
+    pub administrative_regions: Vec<AdminRegion>,
+}
+
+impl FromWithLang<places::poi::Poi> for Poi {
+    fn from_with_lang(poi: places::poi::Poi, langs: &[String]) -> Self {
+        let name = localized(&poi.names, langs, &poi.name);
+        let label = localized(&poi.labels, langs, &poi.label);
+        Poi {
+            name: name.to_string(),
+            label: label.to_string(),
+            properties: key_values(&poi.properties),
+            administrative_regions: admin_regions(&poi.administrative_regions, langs),
+            id: poi.id,
+            coord: poi.coord.into(),
+            distance: poi.distance,
+            poi_type: PoiType {
+                id: poi.poi_type.id,
+                name: poi.poi_type.name,
+            },
+            address: poi.address.map(|address| match address {
+                places::Address::Street(street) => {
+                    Address::Street(Street::from_with_lang(street, langs))
+                }
+                places::Address::Addr(addr) => Address::Addr(Addr::from_with_lang(addr, langs)),
+            }),
+            zip_codes: poi.zip_codes,
+            country_codes: poi.country_codes,
+        }
     }
+}
 
-    async fn explain_geocoder(
-        &self,
-        context: &Context<'_>,
-        q: String,
-        id: String,
-        filters: InputFilters,
-        settings: Upload,
-    ) -> FieldResult<ExplainResponseBody> {
-        let client = get_client_from_context(context)?;
-
-        // Read settings from uploaded file
-        let settings = settings
-            .value(context)
-            .map_err(|err| to_err("extract settings from upload", "graphql", err.to_string()))?;
-
-        // FIXME Use the new QuerySettings::new_from_file function
-        let mut settings_content = String::new();
-        let mut settings_file = tokio::fs::File::from_std(settings.content);
-        settings_file
-            .read_to_string(&mut settings_content)
-            .await
-            .map_err(|err| to_err("read settings from content", "graphql", err.to_string()))?;
-        let settings = QuerySettings::new(&settings_content)
-            .map_err(|err| to_err("invalid settings", "graphql", err.to_string()))?;
-
-        let filters = Filters::from(filters);
-        let dsl = build_query(&q, filters, &["fr"], &settings);
-
-        // When we go about explaining, its about the behavior of a query against a particular
-        // document. So we need to give the name of the index, and the id of the document. We
-        // already have the id, and we'll extract the index from the id, because the id is prefixed
-        // by the document type.
-        let endb = id.find(':').unwrap();
-        let doc_type = id[0..endb].to_string();
-        let res = client
-            .explain_document(SearchQuery::QueryDSL(dsl), id, doc_type)
-            .await?;
-        println!("res: {:?}", res);
-        Ok(res.into())
+#[derive(Debug, SimpleObject)]
+pub struct Mode {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct Line {
+    pub id: String,
+    pub name: String,
+    pub code: Option<String>,
+    pub network: Option<String>,
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct Stop {
+    pub id: String,
+    pub name: String,
+    pub label: String,
+    pub coord: Coord,
+    /// Distance, in meters, to the location given in the query.
+    pub distance: Option<u32>,
+    pub timezone: String,
+    pub codes: Vec<KeyValue>,
+    pub properties: Vec<KeyValue>,
+    pub lines: Vec<Line>,
+    pub commercial_modes: Vec<Mode>,
+    pub physical_modes: Vec<Mode>,
+    pub zip_codes: Vec<String>,
+    pub country_codes: Vec<String>,
+    pub administrative_regions: Vec<AdminRegion>,
+}
+
+impl FromWithLang<places::stop::Stop> for Stop {
+    fn from_with_lang(stop: places::stop::Stop, langs: &[String]) -> Self {
+        Stop {
+            codes: key_values(&stop.codes),
+            properties: key_values(&stop.properties),
+            administrative_regions: admin_regions(&stop.administrative_regions, langs),
+            id: stop.id,
+            name: stop.name,
+            label: stop.label,
+            coord: stop.coord.into(),
+            distance: stop.distance,
+            timezone: stop.timezone,
+            lines: stop
+                .lines
+                .into_iter()
+                .map(|line| Line {
+                    id: line.id,
+                    name: line.name,
+                    code: line.code,
+                    network: line.network.map(|network| network.name),
+                })
+                .collect(),
+            commercial_modes: stop
+                .commercial_modes
+                .into_iter()
+                .map(|mode| Mode {
+                    id: mode.id,
+                    name: mode.name,
+                })
+                .collect(),
+            physical_modes: stop
+                .physical_modes
+                .into_iter()
+                .map(|mode| Mode {
+                    id: mode.id,
+                    name: mode.name,
+                })
+                .collect(),
+            zip_codes: stop.zip_codes,
+            country_codes: stop.country_codes,
+        }
     }
 }
 
-pub type BragiSchema = Schema<Query, Mutation, EmptySubscription>;
-
-#[allow(clippy::borrowed_box)]
-pub fn get_client_from_context<'ctx>(
-    context: &'ctx Context,
-) -> Result<&'ctx ElasticsearchStorage, async_graphql::Error>
-where
-{
-    context.data()
-}
-
-// #[cfg(test)]
-// mod tests {
-//     use super::mimir;
-//     use super::*;
-//     use serde_json::Value;
-//     use std::convert::Infallible;
-//     use warp::Filter;
-//
-//     // TODO How to create a function to return graphql_post, so we don't repeat it.
-//     #[tokio::test]
-//     async fn test_add_index() {
-//         let mut service = mimir::MockMimirService::new();
-//         service.expect_generate_index().times(1).returning(|name| {
-//             Ok(mimir::Index {
-//                 name: String::from(name),
-//             })
-//         });
-//
-//         let schema = schema(Box::new(service));
-//
-//         let graphql_post = async_graphql_warp::graphql(schema).and_then(
-//             |(schema, request): (IndexSchema, async_graphql::Request)| async move {
-//                 Ok::<_, Infallible>(async_graphql_warp::Response::from(
-//                     schema.execute(request).await,
-//                 ))
-//             },
-//         );
-//         let query = r#" "mutation createIndex($index: IndexConfig!) { createIndex(index: $index) { name, status, docsCount } }" "#;
-//         let variables = r#" { "name": "foo" }"#;
-//         let body = format!(
-//             r#"{{ "query": {query}, "variables": {{ "index": {variables} }} }}"#,
-//             query = query,
-//             variables = variables
-//         );
-//
-//         let resp = warp::test::request()
-//             .method("POST")
-//             .body(body)
-//             .reply(&graphql_post)
-//             .await;
-//
-//         assert_eq!(resp.status(), 200);
-//         let data = resp.into_body();
-//         let v: Value = serde_json::from_slice(&data).expect("json");
-//         let c: mimir::Index =
-//             serde_json::from_value(v["data"]["createIndex"].to_owned()).expect("index");
-//         assert_eq!(c.name, "foo");
-//         assert_eq!(c.status, "open");
-//         assert_eq!(c.docs_count, 0);
-//     }
-// }
-//
-fn to_err(
-    context: impl AsRef<str>,
-    reason: impl AsRef<str>,
-    details: String,
-) -> async_graphql::Error {
-    let mut extensions = async_graphql::ErrorExtensionValues::default();
-    extensions.set(reason, details);
-    async_graphql::Error {
-        message: context.as_ref().to_string(),
-        extensions: Some(extensions),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_expose_typed_places() {
+        let schema = schema_builder().finish();
+        let sdl = schema.sdl();
+        assert!(sdl.contains("union Place = | Admin | Street | Addr | Poi | Stop"));
+        assert!(sdl.contains("autocomplete(") && sdl.contains("[AutocompleteResult!]!"));
+    }
+
+    #[tokio::test]
+    async fn should_report_invalid_autocomplete_query() {
+        // The query is validated before the client is used, so there is no need for one.
+        let schema = schema_builder().finish();
+        let resp = schema
+            .execute(r#"{ autocomplete(q: "") { __typename } }"#)
+            .await;
+        assert_eq!(resp.errors.len(), 1);
+        assert!(resp.errors[0].message.contains("non-empty query string"));
+    }
+
+    #[tokio::test]
+    async fn should_limit_the_number_of_searches() {
+        // The complexity is checked before the query runs, so there is no need for a client.
+        let schema = schema_builder().finish();
+        let fields: String = (0..10)
+            .map(|i| format!(r#"q{}: autocomplete(q: "paris") {{ confidence }} "#, i))
+            .collect();
+        let resp = schema.execute(format!("{{ {} }}", fields)).await;
+        assert_eq!(resp.errors.len(), 1);
+        assert!(resp.errors[0].message.contains("too complex"));
     }
 }
@@ -1,30 +1,48 @@
+use crate::adapters::primary::bragi::gql::{BragiSchema, Langs};
+use crate::adapters::primary::bragi::openapi;
 use crate::adapters::primary::bragi::prometheus_handler;
+use crate::adapters::primary::bragi::routes::ApiError;
+use async_graphql_warp::GraphQLResponse;
+use futures::stream::{self, StreamExt};
 use geo::algorithm::haversine_distance::HaversineDistance;
 use geojson::Geometry;
-use std::time::Duration;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::{Duration, Instant};
 use tracing::{debug, instrument};
 use warp::reply::{json, with_status};
 use warp::{http::StatusCode, reject::Reject};
 
-use crate::adapters::primary::bragi::api::{FeaturesQuery, ForwardGeocoderExplainQuery};
+use crate::adapters::primary::bragi::api::{
+    AnalyzeQuery, AnalyzeResponseBody, CodesQuery, FeatureItemResponse, FeaturesBatchQuery,
+    FeaturesQuery, FeaturesResponseBody, ForwardGeocoderExplainQuery, GeometryFormat, NearbyQuery,
+    OutputFormat, ZonesQuery, DEFAULT_ANALYZED_FIELDS, DEFAULT_LANG,
+    DEFAULT_LIMIT_RESULT_REVERSE_API, DEFAULT_LIMIT_RESULT_ZONES,
+};
+use crate::adapters::primary::common::confidence::{ScoredPlace, Scorer};
+use crate::adapters::primary::common::coordinates::{self, Coordinates};
 use crate::adapters::primary::common::dsl::QueryType;
 use crate::adapters::primary::{
     bragi::api::{
-        BragiStatus, ElasticsearchStatus, ForwardGeocoderQuery, MimirStatus, ReverseGeocoderQuery,
-        StatusResponseBody, Type,
+        BatchItemResponse, BatchResponseBody, BragiStatus, ElasticsearchStatus,
+        ForwardGeocoderQuery, MimirStatus, ReverseGeocoderQuery, StatusResponseBody, Type,
     },
     common::{
-        coord, dsl, filters, geocoding::Feature, geocoding::FromWithLang,
-        geocoding::GeocodeJsonResponse, settings,
+        coord, dedup, dsl, filters, geocoding::place_geometry, geocoding::Feature,
+        geocoding::FromWithLang, geocoding::GeocodeJsonResponse, merge, nominatim::NominatimPlace,
+        nominatim::NominatimPlaceV2, pelias::PeliasResponse, settings,
     },
 };
 use crate::domain::model::configuration::{root_doctype, root_doctype_dataset};
+use crate::domain::model::explanation::Explanation;
+use crate::domain::model::hit::Hit;
 use crate::domain::model::query::Query;
+use crate::domain::ports::primary::analyze_text::AnalyzeText;
 use crate::domain::ports::primary::explain_query::ExplainDocument;
 use crate::domain::ports::primary::get_documents::GetDocuments;
 use crate::domain::ports::primary::search_documents::SearchDocuments;
 use crate::domain::ports::primary::status::Status;
 use common::document::ContainerDocument;
+use places::context::{self, Context};
 use places::{addr::Addr, admin::Admin, poi::Poi, stop::Stop, street::Street, Place};
 use serde::{Deserialize, Serialize};
 
@@ -46,10 +64,21 @@ pub struct InternalError {
 
 impl Reject for InternalError {}
 
-pub fn build_feature(
-    places: Vec<places::Place>,
-    query_coord: Option<&coord::Coord>,
-) -> Vec<Feature> {
+impl From<InternalError> for ApiError {
+    fn from(err: InternalError) -> Self {
+        let short = match err.reason {
+            InternalErrorReason::ObjectNotFoundError => "Unable to find object".to_string(),
+            _ => "query error".to_string(),
+        };
+        ApiError {
+            short,
+            long: err.info,
+        }
+    }
+}
+
+/// Sets, on each place, its distance to the coordinates given in the query, if any.
+pub fn with_distance(places: Vec<Place>, query_coord: Option<&coord::Coord>) -> Vec<Place> {
     places
         .into_iter()
         .map(|mut p| {
@@ -59,46 +88,238 @@ pub fn build_feature(
                 let distance = geo_point.haversine_distance(&pp) as u32;
                 p.set_distance(distance);
             }
-            Feature::from_with_lang(p, None)
+            p
         })
         .collect()
 }
 
-#[instrument(skip(client, settings))]
+/// Returns the languages in which names and labels are looked for: the language
+/// given in the query, if any, followed by the languages of the request.
+pub fn resolve_langs(query_lang: Option<String>, langs: Vec<String>) -> Vec<String> {
+    let mut resolved: Vec<String> = query_lang.into_iter().collect();
+    for lang in langs {
+        if !resolved.contains(&lang) {
+            resolved.push(lang);
+        }
+    }
+    resolved
+}
+
+/// Serializes places in the requested format. `text` is the query string, if any.
+pub fn places_reply(
+    places: Vec<Place>,
+    text: Option<String>,
+    format: OutputFormat,
+    langs: &[String],
+) -> warp::reply::Json {
+    let features: Vec<Feature> = places
+        .into_iter()
+        .map(|p| Feature::from_with_lang(p, langs))
+        .collect();
+    features_reply(features, text, format)
+}
+
+/// Serializes features in the requested format. `text` is the query string, if any.
+pub fn features_reply(
+    features: Vec<Feature>,
+    text: Option<String>,
+    format: OutputFormat,
+) -> warp::reply::Json {
+    match format {
+        OutputFormat::GeocodeJson => json(&GeocodeJsonResponse::new(
+            text.unwrap_or_default(),
+            features,
+        )),
+        OutputFormat::Nominatim => json(
+            &features
+                .into_iter()
+                .map(NominatimPlace::from)
+                .collect::<Vec<_>>(),
+        ),
+        OutputFormat::NominatimV2 => json(
+            &features
+                .into_iter()
+                .map(NominatimPlaceV2::from)
+                .collect::<Vec<_>>(),
+        ),
+        OutputFormat::Pelias => json(&PeliasResponse::new(text, features)),
+    }
+}
+
+#[instrument(skip(client, profile))]
 pub async fn forward_geocoder<S>(
     params: ForwardGeocoderQuery,
     geometry: Option<Geometry>,
     client: S,
-    settings: settings::QuerySettings,
+    profile: settings::Profile,
     timeout: Duration,
+    langs: Vec<String>,
 ) -> Result<impl warp::Reply, warp::Rejection>
+where
+    S: SearchDocuments,
+    S::Document: Serialize + Into<serde_json::Value>,
+{
+    let start = Instant::now();
+    let settings = &profile.settings;
+    let langs = resolve_langs(params.lang.clone(), langs);
+    let q = params.full_text();
+    let format = params.format;
+    // Coordinates typed in the search box would only give meaningless fuzzy matches,
+    // so they are reverse geocoded instead.
+    let features = match coordinates::parse(&params.q) {
+        Some(coordinates) => {
+            coordinates_features(&client, coordinates, params, settings, timeout, &langs).await
+        }
+        None => forward_geocode_places(&client, params, geometry, settings, timeout, &langs)
+            .await
+            .map(|places| forward_geocode_features(places, &langs)),
+    };
+    // Failed queries are recorded as empty responses.
+    prometheus_handler::observe_profile(
+        "autocomplete",
+        &profile.name,
+        start.elapsed(),
+        features.as_ref().map_or(true, Vec::is_empty),
+    );
+    match features {
+        Ok(features) => Ok(with_status(
+            features_reply(features, Some(q), format),
+            StatusCode::OK,
+        )),
+        Err(err) => Err(warp::reject::custom(err)),
+    }
+}
+
+/// Builds the features of the places found by the forward geocoder, with their confidence
+/// and the ids of the places collapsed into them.
+pub fn forward_geocode_features(places: Vec<ScoredPlace>, langs: &[String]) -> Vec<Feature> {
+    places
+        .into_iter()
+        .map(|scored| {
+            let mut feature = Feature {
+                collapsed_ids: scored.collapsed_ids,
+                ..Feature::from_with_lang(scored.place, langs)
+            };
+            feature.properties.geocoding.confidence = Some(scored.confidence);
+            feature
+        })
+        .collect()
+}
+
+/// Answers a forward geocoding query made of coordinates: a feature for the coordinates
+/// themselves comes first, followed by the places found around them by the reverse geocoder.
+pub async fn coordinates_features<S>(
+    client: &S,
+    coordinates: Coordinates,
+    params: ForwardGeocoderQuery,
+    settings: &settings::QuerySettings,
+    timeout: Duration,
+    langs: &[String],
+) -> Result<Vec<Feature>, InternalError>
+where
+    S: SearchDocuments,
+    S::Document: Serialize + Into<serde_json::Value>,
+{
+    let reverse = ReverseGeocoderQuery {
+        lat: coordinates.lat,
+        lon: coordinates.lon,
+        types: params.types,
+        limit: DEFAULT_LIMIT_RESULT_REVERSE_API,
+        timeout: params.timeout,
+        pt_dataset: params.pt_dataset,
+        poi_dataset: params.poi_dataset,
+        ..Default::default()
+    };
+    let coord = coord::Coord::new(coordinates.lat as f32, coordinates.lon as f32);
+    let places = reverse_geocode_places(client, reverse, settings, timeout).await?;
+    let mut features = vec![Feature::from_coordinates(coordinates)];
+    features.extend(
+        with_distance(places, Some(&coord))
+            .into_iter()
+            .map(|place| Feature::from_with_lang(place, langs)),
+    );
+    Ok(features)
+}
+
+/// Runs a single forward geocoding query against the backend.
+pub async fn forward_geocode<S>(
+    client: &S,
+    params: ForwardGeocoderQuery,
+    geometry: Option<Geometry>,
+    settings: &settings::QuerySettings,
+    timeout: Duration,
+    langs: &[String],
+) -> Result<GeocodeJsonResponse, InternalError>
+where
+    S: SearchDocuments,
+    S::Document: Serialize + Into<serde_json::Value>,
+{
+    let q = params.full_text();
+    let places = forward_geocode_places(client, params, geometry, settings, timeout, langs).await?;
+    let features = forward_geocode_features(places, langs);
+    Ok(GeocodeJsonResponse::new(q, features))
+}
+
+/// Searches the places matching a forward geocoding query, with their confidence.
+///
+/// Both a prefix and a fuzzy query are built, and their results are combined according
+/// to the merge strategy of the query settings. Near-identical places are collapsed
+/// before the quotas and the limit are applied, so that they don't shorten the response.
+pub async fn forward_geocode_places<S>(
+    client: &S,
+    params: ForwardGeocoderQuery,
+    geometry: Option<Geometry>,
+    settings: &settings::QuerySettings,
+    timeout: Duration,
+    langs: &[String],
+) -> Result<Vec<ScoredPlace>, InternalError>
 where
     S: SearchDocuments,
     S::Document: Serialize + Into<serde_json::Value>,
 {
     let q = params.q.clone();
+    let full_text = params.full_text();
+    let house_number = params.house_number.clone();
+    let min_confidence = params.min_confidence.unwrap_or(0.0);
     let timeout = params.timeout.unwrap_or(timeout);
     let es_indices_to_search_in =
         build_es_indices_to_search(&params.types, &params.pt_dataset, &params.poi_dataset);
-    let lang = params.lang.clone();
-    let filters = filters::Filters::from((params, geometry));
+    let lang = langs.first().map_or(DEFAULT_LANG, String::as_str);
+    let quotas = type_quotas(&settings.quotas, params.max_per_type.as_ref());
+    let debug = params.debug;
+    let mut filters = filters::Filters::from((params, geometry));
+    let limit = filters.limit.max(0) as usize;
+    // Places over their quota, and near-identical places, are dropped, so more places
+    // are needed to fill the response.
+    if !quotas.is_empty() || settings.dedup.enabled {
+        filters.limit = filters
+            .limit
+            .saturating_mul(i64::from(settings.quotas.overfetch.max(1)));
+    }
     let excludes = vec!["boundary".to_string()];
-    let dsl_query_prefix = dsl::build_query(
+    let mut dsl_query_prefix = dsl::build_query(
         &q,
         filters.clone(),
-        lang.as_str(),
-        &settings,
+        lang,
+        settings,
         QueryType::PREFIX,
         &Option::Some(excludes.clone()),
     );
-    let dsl_query_fuzzy = dsl::build_query(
+    let mut dsl_query_fuzzy = dsl::build_query(
         &q,
         filters.clone(),
-        lang.as_str(),
-        &settings,
+        lang,
+        settings,
         QueryType::FUZZY,
         &Option::Some(excludes),
     );
+    if debug {
+        for dsl in [&mut dsl_query_prefix, &mut dsl_query_fuzzy] {
+            if let Some(dsl) = dsl.as_object_mut() {
+                dsl.insert("explain".to_string(), serde_json::Value::Bool(true));
+            }
+        }
+    }
 
     tracing::trace!(
         "Searching in indexes {:?} with query {}",
@@ -106,113 +327,361 @@ where
         serde_json::to_string_pretty(&dsl_query_prefix).unwrap()
     );
 
-    let futurs = vec![
-        client.search_documents(
-            es_indices_to_search_in.clone(),
-            Query::QueryDSL(dsl_query_prefix.clone()),
-            filters.limit,
-            Some(timeout),
-        ),
-        client.search_documents(
-            es_indices_to_search_in.clone(),
-            Query::QueryDSL(dsl_query_fuzzy),
+    // Both queries are sent in a single request, and their results are merged
+    // according to the merge strategy.
+    let mut hits = client
+        .multi_search_documents(
+            es_indices_to_search_in,
+            vec![
+                Query::QueryDSL(dsl_query_prefix),
+                Query::QueryDSL(dsl_query_fuzzy),
+            ],
             filters.limit,
             Some(timeout),
-        ),
-    ];
-    for futur in futurs {
-        match futur.await {
-            Ok(res) => {
-                let places: Result<Vec<Place>, serde_json::Error> = res
-                    .into_iter()
-                    .map(|json| serde_json::from_value::<Place>(json.into()))
-                    .collect();
-                match places {
-                    Ok(places) if places.is_empty() => {}
-                    Ok(places) => {
-                        let features = build_feature(places, filters.coord.as_ref());
-                        let resp = GeocodeJsonResponse::new(q, features);
-                        return Ok(with_status(json(&resp), StatusCode::OK));
-                    }
-                    Err(err) => {
-                        return Err(warp::reject::custom(InternalError {
-                            reason: InternalErrorReason::SerializationError,
-                            info: err.to_string(),
-                        }))
+        )
+        .await
+        .map_err(|err| InternalError {
+            reason: InternalErrorReason::ElasticSearchError,
+            info: err.to_string(),
+        })?
+        .into_iter()
+        .zip([QueryType::PREFIX, QueryType::FUZZY])
+        .map(|(hits, query_type)| {
+            hits.into_iter()
+                .map(|hit| {
+                    let Hit {
+                        document,
+                        score,
+                        explanation,
+                    } = hit;
+                    serde_json::from_value::<Place>(document.into()).map(|mut place| {
+                        if let Some(explanation) = explanation {
+                            place.set_context(Context {
+                                explanation: Some(query_explanation(query_type, explanation)),
+                            });
+                        }
+                        Hit {
+                            document: place,
+                            score,
+                            explanation: None,
+                        }
+                    })
+                })
+                .collect::<Result<Vec<Hit<Place>>, serde_json::Error>>()
+        })
+        .collect::<Result<Vec<_>, serde_json::Error>>()
+        .map_err(|err| InternalError {
+            reason: InternalErrorReason::SerializationError,
+            info: err.to_string(),
+        })?;
+    let fuzzy = hits.pop().unwrap_or_default();
+    let prefix = hits.pop().unwrap_or_default();
+    // Ids of the places found by the prefix query, the others were only found by the
+    // fuzzy query.
+    let prefix_ids: HashSet<String> = prefix
+        .iter()
+        .map(|hit| hit.document.id().to_string())
+        .collect();
+    // With a structured address, only the prefix query requires the places to match each
+    // component, so the fuzzy query is a fallback for when it finds nothing, whatever the
+    // merge strategy.
+    let merge_strategy = if filters.address.is_some() {
+        settings::MergeStrategy::FirstNonEmpty
+    } else {
+        settings.merge_strategy
+    };
+    let places = merge::merge_places(prefix, fuzzy, merge_strategy, filters.limit.max(0) as usize);
+
+    let scorer = Scorer::new(&full_text, house_number.as_deref(), filters.coord.as_ref());
+    let places = with_distance(places, filters.coord.as_ref())
+        .into_iter()
+        .map(|place| {
+            let query_type = if prefix_ids.contains(place.id()) {
+                QueryType::PREFIX
+            } else {
+                QueryType::FUZZY
+            };
+            ScoredPlace {
+                confidence: scorer.confidence(&place, query_type),
+                place,
+                collapsed_ids: Vec::new(),
+            }
+        })
+        .filter(|place| place.confidence >= min_confidence)
+        .collect();
+    let places = dedup::collapse_places(places, &settings.dedup);
+    Ok(merge::apply_quotas(places, &quotas, limit))
+}
+
+// The explanation of the score of a hit, under a root telling which query found it.
+fn query_explanation(query_type: QueryType, explanation: Explanation) -> context::Explanation {
+    let explanation = context::Explanation::from(explanation);
+    context::Explanation {
+        value: explanation.value,
+        description: match query_type {
+            QueryType::PREFIX => "prefix query".to_string(),
+            QueryType::FUZZY => "fuzzy query".to_string(),
+        },
+        details: vec![explanation],
+    }
+}
+
+// The quotas of the query settings, overridden by those of the request.
+fn type_quotas(
+    settings: &settings::Quotas,
+    max_per_type: Option<&BTreeMap<Type, usize>>,
+) -> BTreeMap<String, usize> {
+    let mut quotas = settings.max_per_type.clone();
+    for (place_type, quota) in max_per_type.into_iter().flatten() {
+        let place_type = match place_type {
+            Type::City => Type::Zone,
+            place_type => *place_type,
+        };
+        quotas.insert(place_type.as_str().to_string(), *quota);
+    }
+    quotas
+}
+
+/// Runs each query of a batch through the forward geocoder.
+///
+/// At most `concurrency` queries are sent to the backend at the same time. Items
+/// are answered in the order they were submitted, and an item that could not be
+/// validated or geocoded gets its own error, without failing the whole batch.
+#[instrument(skip(items, client, profile))]
+pub async fn forward_geocoder_batch<S>(
+    items: Vec<Result<ForwardGeocoderQuery, ApiError>>,
+    client: S,
+    profile: settings::Profile,
+    timeout: Duration,
+    concurrency: usize,
+    langs: Vec<String>,
+) -> Result<impl warp::Reply, warp::Rejection>
+where
+    S: SearchDocuments,
+    S::Document: Serialize + Into<serde_json::Value>,
+{
+    let client = &client;
+    let settings = &*profile.settings;
+    let langs = &langs;
+    let results: Vec<BatchItemResponse> = stream::iter(items)
+        .map(|item| async move {
+            match item {
+                Ok(params) => {
+                    let langs = resolve_langs(params.lang.clone(), langs.clone());
+                    match forward_geocode(client, params, None, settings, timeout, &langs).await {
+                        Ok(resp) => BatchItemResponse::Response(resp),
+                        Err(err) => BatchItemResponse::Error {
+                            error: ApiError::from(err),
+                        },
                     }
                 }
+                Err(error) => BatchItemResponse::Error { error },
             }
-            Err(err) => {
-                return Err(warp::reject::custom(InternalError {
-                    reason: InternalErrorReason::ElasticSearchError,
-                    info: err.to_string(),
-                }))
-            }
-        }
-    }
+        })
+        .buffered(concurrency.max(1))
+        .collect()
+        .await;
 
     Ok(with_status(
-        json(&GeocodeJsonResponse::new(q, vec![])),
+        json(&BatchResponseBody { results }),
         StatusCode::OK,
     ))
 }
 
-#[instrument(skip(client, settings))]
+#[instrument(skip(client, profile))]
 pub async fn forward_geocoder_explain<S>(
     params: ForwardGeocoderExplainQuery,
     geometry: Option<Geometry>,
     client: S,
-    settings: settings::QuerySettings,
+    profile: settings::Profile,
     timeout: Duration,
+    langs: Vec<String>,
 ) -> Result<impl warp::Reply, warp::Rejection>
+where
+    S: ExplainDocument,
+    S::Document: Serialize + Into<serde_json::Value>,
+{
+    let langs = resolve_langs(params.query.lang.clone(), langs);
+    match explain_forward_geocode(&client, params, geometry, &profile.settings, &langs).await {
+        Ok(res) => Ok(with_status(json(&res), StatusCode::OK)),
+        Err(err) => Err(warp::reject::custom(err)),
+    }
+}
+
+/// Explains how the prefix query of a forward geocoding query scores a given document.
+pub async fn explain_forward_geocode<S>(
+    client: &S,
+    params: ForwardGeocoderExplainQuery,
+    geometry: Option<Geometry>,
+    settings: &settings::QuerySettings,
+    langs: &[String],
+) -> Result<serde_json::Value, InternalError>
 where
     S: ExplainDocument,
     S::Document: Serialize + Into<serde_json::Value>,
 {
     let q = params.query.q.clone();
-    let lang = params.query.lang.clone();
+    let lang = langs.first().map_or(DEFAULT_LANG, String::as_str);
     let filters = filters::Filters::from((params.query, geometry));
     let dsl = dsl::build_query(
         &q,
         filters,
-        lang.as_str(),
-        &settings,
+        lang,
+        settings,
         QueryType::PREFIX,
         &Option::None,
     );
 
     debug!("{}", serde_json::to_string(&dsl).unwrap());
 
-    match client
+    client
         .explain_document(Query::QueryDSL(dsl), params.doc_id, params.doc_type)
         .await
-    {
-        Ok(res) => Ok(with_status(json(&res), StatusCode::OK)),
-        Err(err) => Err(warp::reject::custom(InternalError {
+        .map(Into::into)
+        .map_err(|err| InternalError {
             reason: InternalErrorReason::ElasticSearchError,
             info: err.to_string(),
-        })),
+        })
+}
+
+#[instrument(skip(client))]
+pub async fn analyze<S>(
+    params: AnalyzeQuery,
+    client: S,
+) -> Result<impl warp::Reply, warp::Rejection>
+where
+    S: AnalyzeText,
+{
+    match analyze_fields(&client, params).await {
+        Ok(res) => Ok(with_status(json(&res), StatusCode::OK)),
+        Err(err) => Err(warp::reject::custom(err)),
+    }
+}
+
+/// Analyzes a text with the analyzer of each requested field, or of the fields searched
+/// by autocomplete.
+pub async fn analyze_fields<S>(
+    client: &S,
+    params: AnalyzeQuery,
+) -> Result<AnalyzeResponseBody, InternalError>
+where
+    S: AnalyzeText,
+{
+    let fields = params.field.unwrap_or_else(|| {
+        DEFAULT_ANALYZED_FIELDS
+            .iter()
+            .map(|field| field.to_string())
+            .collect()
+    });
+    let mut analysis = BTreeMap::new();
+    for field in fields {
+        let tokens = client
+            .analyze_text(params.q.clone(), field.clone(), params.doc_type.clone())
+            .await
+            .map_err(|err| InternalError {
+                reason: InternalErrorReason::ElasticSearchError,
+                info: err.to_string(),
+            })?;
+        analysis.insert(field, tokens);
     }
+    Ok(AnalyzeResponseBody { fields: analysis })
 }
 
 pub async fn reverse_geocoder<S>(
     params: ReverseGeocoderQuery,
     client: S,
-    settings: settings::QuerySettings,
+    profile: settings::Profile,
+    timeout: Duration,
+    langs: Vec<String>,
+) -> Result<impl warp::Reply, warp::Rejection>
+where
+    S: SearchDocuments,
+    S::Document: Serialize + Into<serde_json::Value>,
+{
+    let start = Instant::now();
+    let langs = resolve_langs(params.lang.clone(), langs);
+    let format = params.format;
+    let places = reverse_geocode_places(&client, params, &profile.settings, timeout).await;
+    // Failed queries are recorded as empty responses.
+    prometheus_handler::observe_profile(
+        "reverse",
+        &profile.name,
+        start.elapsed(),
+        places.as_ref().map_or(true, Vec::is_empty),
+    );
+    match places {
+        Ok(places) => {
+            let reply = match format {
+                // Nominatim answers a reverse query with a single place
+                OutputFormat::Nominatim | OutputFormat::NominatimV2 => {
+                    match places.into_iter().next() {
+                        Some(place) => {
+                            let feature = Feature::from_with_lang(place, &langs);
+                            if format == OutputFormat::Nominatim {
+                                json(&NominatimPlace::from(feature))
+                            } else {
+                                json(&NominatimPlaceV2::from(feature))
+                            }
+                        }
+                        None => json(&serde_json::json!({ "error": "Unable to geocode" })),
+                    }
+                }
+                _ => places_reply(places, None, format, &langs),
+            };
+            Ok(with_status(reply, StatusCode::OK))
+        }
+        Err(err) => Err(warp::reject::custom(err)),
+    }
+}
+
+/// Looks for the pois and stops around a location, the closest first.
+///
+/// Unlike the reverse geocoder, the distance to the location is given for each place.
+#[instrument(skip(client))]
+pub async fn nearby<S>(
+    params: NearbyQuery,
+    client: S,
     timeout: Duration,
+    langs: Vec<String>,
 ) -> Result<impl warp::Reply, warp::Rejection>
+where
+    S: SearchDocuments,
+    S::Document: Serialize + Into<serde_json::Value>,
+{
+    let langs = resolve_langs(params.lang.clone(), langs);
+    let format = params.format;
+    match nearby_places(&client, params, timeout).await {
+        Ok(places) => Ok(with_status(
+            places_reply(places, None, format, &langs),
+            StatusCode::OK,
+        )),
+        Err(err) => Err(warp::reject::custom(err)),
+    }
+}
+
+/// Searches the pois and stops around the location given in a nearby query.
+pub async fn nearby_places<S>(
+    client: &S,
+    params: NearbyQuery,
+    timeout: Duration,
+) -> Result<Vec<Place>, InternalError>
 where
     S: SearchDocuments,
     S::Document: Serialize + Into<serde_json::Value>,
 {
     let timeout = params.timeout.unwrap_or(timeout);
-    let distance = format!("{}m", settings.reverse_query.radius);
-    let dsl = dsl::build_reverse_query(&distance, params.lat, params.lon);
+    let distance = format!("{}m", params.radius);
+    let types = Some(
+        params
+            .types
+            .unwrap_or_else(|| vec![Type::Poi, Type::StopArea]),
+    );
+    let es_indices_to_search_in =
+        build_es_indices_to_search(&types, &params.pt_dataset, &params.poi_dataset);
+    let coord = coord::Coord::new(params.lat as f32, params.lon as f32);
 
-    let es_indices_to_search_in = vec![
-        root_doctype(Street::static_doc_type()),
-        root_doctype(Addr::static_doc_type()),
-    ];
+    let dsl =
+        dsl::build_reverse_query_with_filters(&distance, params.lat, params.lon, params.poi_types);
 
     tracing::trace!(
         "Searching in indexes {:?} with query {}",
@@ -220,7 +689,7 @@ where
         serde_json::to_string_pretty(&dsl).unwrap()
     );
 
-    match client
+    let places = client
         .search_documents(
             es_indices_to_search_in,
             Query::QueryDSL(dsl),
@@ -228,37 +697,93 @@ where
             Some(timeout),
         )
         .await
-    {
-        Ok(res) => {
-            let places = res
-                .into_iter()
-                .map(|json| serde_json::from_value::<Place>(json.into()).unwrap())
-                .collect();
+        .map_err(|err| InternalError {
+            reason: InternalErrorReason::ElasticSearchError,
+            info: err.to_string(),
+        })?
+        .into_iter()
+        .map(|json| serde_json::from_value::<Place>(json.into()))
+        .collect::<Result<Vec<Place>, serde_json::Error>>()
+        .map_err(|err| InternalError {
+            reason: InternalErrorReason::SerializationError,
+            info: err.to_string(),
+        })?;
 
-            let resp = GeocodeJsonResponse::from_with_lang(places, None);
-            Ok(with_status(json(&resp), StatusCode::OK))
-        }
-        Err(err) => Err(warp::reject::custom(InternalError {
+    Ok(with_distance(places, Some(&coord)))
+}
+
+/// Searches the places around the location given in a reverse geocoding query.
+pub async fn reverse_geocode_places<S>(
+    client: &S,
+    params: ReverseGeocoderQuery,
+    settings: &settings::QuerySettings,
+    timeout: Duration,
+) -> Result<Vec<Place>, InternalError>
+where
+    S: SearchDocuments,
+    S::Document: Serialize + Into<serde_json::Value>,
+{
+    let timeout = params.timeout.unwrap_or(timeout);
+    let radius = params.radius.unwrap_or(settings.reverse_query.radius);
+    let distance = format!("{}m", radius);
+
+    // Without any explicit type, we keep on looking only for streets and addresses,
+    // which is what most clients of the reverse geocoder expect.
+    let types = params
+        .types
+        .clone()
+        .or_else(|| Some(vec![Type::Street, Type::House]));
+    let es_indices_to_search_in =
+        build_es_indices_to_search(&types, &params.pt_dataset, &params.poi_dataset);
+
+    let dsl =
+        dsl::build_reverse_query_with_filters(&distance, params.lat, params.lon, params.poi_types);
+
+    tracing::trace!(
+        "Searching in indexes {:?} with query {}",
+        es_indices_to_search_in,
+        serde_json::to_string_pretty(&dsl).unwrap()
+    );
+
+    client
+        .search_documents(
+            es_indices_to_search_in,
+            Query::QueryDSL(dsl),
+            params.limit,
+            Some(timeout),
+        )
+        .await
+        .map_err(|err| InternalError {
             reason: InternalErrorReason::ElasticSearchError,
             info: err.to_string(),
-        })),
-    }
+        })?
+        .into_iter()
+        .map(|json| serde_json::from_value::<Place>(json.into()))
+        .collect::<Result<Vec<Place>, serde_json::Error>>()
+        .map_err(|err| InternalError {
+            reason: InternalErrorReason::SerializationError,
+            info: err.to_string(),
+        })
 }
 
-pub async fn features<S>(
-    doc_id: String,
-    params: FeaturesQuery,
+pub async fn zones<S>(
+    params: ZonesQuery,
     client: S,
     timeout: Duration,
+    langs: Vec<String>,
 ) -> Result<impl warp::Reply, warp::Rejection>
 where
-    S: GetDocuments,
+    S: SearchDocuments,
     S::Document: Serialize + Into<serde_json::Value>,
 {
+    let langs = resolve_langs(params.lang, langs);
     let timeout = params.timeout.unwrap_or(timeout);
-    let es_indices_to_search_in =
-        build_es_indices_to_search(&None, &params.pt_dataset, &params.poi_dataset);
-    let dsl = dsl::build_features_query(&es_indices_to_search_in, &doc_id);
+    let zone_types = params
+        .zone_types
+        .map(|zts| zts.iter().map(|t| t.as_str().to_string()).collect());
+    let dsl = dsl::build_zones_query(params.lat, params.lon, zone_types);
+
+    let es_indices_to_search_in = vec![root_doctype(Admin::static_doc_type())];
 
     tracing::trace!(
         "Searching in indexes {:?} with query {}",
@@ -267,24 +792,29 @@ where
     );
 
     match client
-        .get_documents_by_id(Query::QueryDSL(dsl), Some(timeout))
+        .search_documents(
+            es_indices_to_search_in,
+            Query::QueryDSL(dsl),
+            DEFAULT_LIMIT_RESULT_ZONES,
+            Some(timeout),
+        )
         .await
     {
         Ok(res) => {
-            let places: Result<Vec<Place>, serde_json::Error> = res
+            let admins: Result<Vec<Admin>, serde_json::Error> = res
                 .into_iter()
-                .map(|json| serde_json::from_value::<Place>(json.into()))
+                .map(|json| serde_json::from_value::<Admin>(json.into()))
                 .collect();
 
-            match places {
-                Ok(places) if places.is_empty() => Err(warp::reject::custom(InternalError {
-                    reason: InternalErrorReason::ObjectNotFoundError,
-                    info: "Unable to find object".to_string(),
-                })),
-                Ok(places) => {
-                    let features: Vec<Feature> = places
+            match admins {
+                Ok(mut admins) => {
+                    // From the most local admin (suburb) to the most global one (country)
+                    admins.sort_by_key(|admin| {
+                        (zone_type_rank(admin), std::cmp::Reverse(admin.level))
+                    });
+                    let features: Vec<Feature> = admins
                         .into_iter()
-                        .map(|p| Feature::from_with_lang(p, None)) // FIXME lang: None
+                        .map(|admin| Feature::from_with_lang(Place::Admin(admin), &langs))
                         .collect();
                     let resp = GeocodeJsonResponse::new("".to_string(), features);
                     Ok(with_status(json(&resp), StatusCode::OK))
@@ -302,7 +832,266 @@ where
     }
 }
 
-pub async fn status<S>(client: S, url: String) -> Result<impl warp::Reply, warp::Rejection>
+// Ranks the admins according to their zone type, the smallest zones first.
+// Admins without zone type come last.
+fn zone_type_rank(admin: &Admin) -> usize {
+    match admin.zone_type.as_ref().map(|zone_type| zone_type.as_str()) {
+        Some("suburb") => 0,
+        Some("city_district") => 1,
+        Some("city") => 2,
+        Some("state_district") => 3,
+        Some("state") => 4,
+        Some("country_region") => 5,
+        Some("country") => 6,
+        _ => 7,
+    }
+}
+
+pub async fn features<S>(
+    doc_id: String,
+    params: FeaturesQuery,
+    client: S,
+    timeout: Duration,
+    langs: Vec<String>,
+) -> Result<impl warp::Reply, warp::Rejection>
+where
+    S: GetDocuments,
+    S::Document: Serialize + Into<serde_json::Value>,
+{
+    let langs = resolve_langs(params.lang.clone(), langs);
+    let format = params.format;
+    let (geometry, tolerance) = (params.geometry, params.simplify);
+    match feature_places(&client, &[doc_id], params, timeout).await {
+        Ok(places) if places.is_empty() => Err(warp::reject::custom(InternalError {
+            reason: InternalErrorReason::ObjectNotFoundError,
+            info: "Unable to find object".to_string(),
+        })),
+        Ok(places) => {
+            // The other formats have their own representation of the extent of places.
+            let features = match format {
+                OutputFormat::GeocodeJson => places
+                    .into_iter()
+                    .map(|place| feature_with_geometry(place, geometry, tolerance, &langs))
+                    .collect(),
+                _ => places
+                    .into_iter()
+                    .map(|place| Feature::from_with_lang(place, &langs))
+                    .collect(),
+            };
+            Ok(with_status(
+                features_reply(features, None, format),
+                StatusCode::OK,
+            ))
+        }
+        Err(err) => Err(warp::reject::custom(err)),
+    }
+}
+
+// Builds the feature of a place, with the requested geometry.
+fn feature_with_geometry(
+    place: Place,
+    geometry: GeometryFormat,
+    tolerance: Option<f64>,
+    langs: &[String],
+) -> Feature {
+    let geometry = place_geometry(&place, geometry, tolerance);
+    Feature {
+        geometry,
+        ..Feature::from_with_lang(place, langs)
+    }
+}
+
+/// Looks for the stops and admins having the given value for a code, like
+/// the UIC code of a stop, or the INSEE code of a city.
+#[instrument(skip(client))]
+pub async fn codes<S>(
+    code_type: String,
+    value: String,
+    params: CodesQuery,
+    client: S,
+    timeout: Duration,
+    langs: Vec<String>,
+) -> Result<impl warp::Reply, warp::Rejection>
+where
+    S: SearchDocuments,
+    S::Document: Serialize + Into<serde_json::Value>,
+{
+    let langs = resolve_langs(params.lang.clone(), langs);
+    let format = params.format;
+    match code_places(&client, &code_type, &value, params, timeout).await {
+        Ok(places) => Ok(with_status(
+            places_reply(places, None, format, &langs),
+            StatusCode::OK,
+        )),
+        Err(err) => Err(warp::reject::custom(err)),
+    }
+}
+
+/// Searches the places having the given value for a code.
+pub async fn code_places<S>(
+    client: &S,
+    code_type: &str,
+    value: &str,
+    params: CodesQuery,
+    timeout: Duration,
+) -> Result<Vec<Place>, InternalError>
+where
+    S: SearchDocuments,
+    S::Document: Serialize + Into<serde_json::Value>,
+{
+    let timeout = params.timeout.unwrap_or(timeout);
+    let types = Some(
+        params
+            .types
+            .unwrap_or_else(|| vec![Type::StopArea, Type::Zone]),
+    );
+    let mut es_indices_to_search_in = build_es_indices_to_search(&types, &params.pt_dataset, &None);
+    // 'zone' and 'city' are both searched in the admins
+    es_indices_to_search_in.sort();
+    es_indices_to_search_in.dedup();
+    let dsl = dsl::build_codes_query(code_type, value);
+
+    tracing::trace!(
+        "Searching in indexes {:?} with query {}",
+        es_indices_to_search_in,
+        serde_json::to_string_pretty(&dsl).unwrap()
+    );
+
+    client
+        .search_documents(
+            es_indices_to_search_in,
+            Query::QueryDSL(dsl),
+            params.limit,
+            Some(timeout),
+        )
+        .await
+        .map_err(|err| InternalError {
+            reason: InternalErrorReason::ElasticSearchError,
+            info: err.to_string(),
+        })?
+        .into_iter()
+        .map(|json| serde_json::from_value::<Place>(json.into()))
+        .collect::<Result<Vec<Place>, serde_json::Error>>()
+        .map_err(|err| InternalError {
+            reason: InternalErrorReason::SerializationError,
+            info: err.to_string(),
+        })
+}
+
+/// Looks for several places by id, with a single query to the backend.
+///
+/// The places are returned in the order of the requested ids, and each id that
+/// could not be found gets its own error.
+pub async fn features_batch<S>(
+    params: FeaturesBatchQuery,
+    client: S,
+    timeout: Duration,
+    langs: Vec<String>,
+) -> Result<impl warp::Reply, warp::Rejection>
+where
+    S: GetDocuments,
+    S::Document: Serialize + Into<serde_json::Value>,
+{
+    let langs = resolve_langs(params.lang.clone(), langs);
+    let ids = params.ids.unwrap_or_default();
+    let (geometry, tolerance) = (params.geometry, params.simplify);
+    let query = FeaturesQuery {
+        pt_dataset: params.pt_dataset,
+        poi_dataset: params.poi_dataset,
+        geometry,
+        timeout: params.timeout,
+        ..Default::default()
+    };
+    match feature_places(&client, &ids, query, timeout).await {
+        Ok(places) => {
+            // A place may be found in several indices, we keep the first one.
+            let mut places_by_id = HashMap::new();
+            for place in places {
+                places_by_id.entry(place.id().to_string()).or_insert(place);
+            }
+            let results = ids
+                .into_iter()
+                .map(|id| match places_by_id.get(&id) {
+                    Some(place) => FeatureItemResponse::Feature(feature_with_geometry(
+                        place.clone(),
+                        geometry,
+                        tolerance,
+                        &langs,
+                    )),
+                    None => FeatureItemResponse::Error {
+                        error: ApiError {
+                            short: "Unable to find object".to_string(),
+                            long: format!("Unable to find object with id {}", id),
+                        },
+                        id,
+                    },
+                })
+                .collect();
+            Ok(with_status(
+                json(&FeaturesResponseBody { results }),
+                StatusCode::OK,
+            ))
+        }
+        Err(err) => Err(warp::reject::custom(err)),
+    }
+}
+
+/// Retrieves the places with the given ids.
+pub async fn feature_places<S>(
+    client: &S,
+    doc_ids: &[String],
+    params: FeaturesQuery,
+    timeout: Duration,
+) -> Result<Vec<Place>, InternalError>
+where
+    S: GetDocuments,
+    S::Document: Serialize + Into<serde_json::Value>,
+{
+    let timeout = params.timeout.unwrap_or(timeout);
+    let es_indices_to_search_in =
+        build_es_indices_to_search(&None, &params.pt_dataset, &params.poi_dataset);
+    let dsl = dsl::build_features_query(
+        &es_indices_to_search_in,
+        doc_ids,
+        params.geometry == GeometryFormat::Full,
+    );
+
+    tracing::trace!(
+        "Searching in indexes {:?} with query {}",
+        es_indices_to_search_in,
+        serde_json::to_string_pretty(&dsl).unwrap()
+    );
+
+    client
+        .get_documents_by_id(Query::QueryDSL(dsl), Some(timeout))
+        .await
+        .map_err(|err| InternalError {
+            reason: InternalErrorReason::ElasticSearchError,
+            info: err.to_string(),
+        })?
+        .into_iter()
+        .map(|json| serde_json::from_value::<Place>(json.into()))
+        .collect::<Result<Vec<Place>, serde_json::Error>>()
+        .map_err(|err| InternalError {
+            reason: InternalErrorReason::SerializationError,
+            info: err.to_string(),
+        })
+}
+
+pub async fn graphql(
+    (schema, request): (BragiSchema, async_graphql::Request),
+    profile: settings::Profile,
+    langs: Vec<String>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let request = request.data(Langs(langs)).data(profile);
+    Ok(GraphQLResponse::from(schema.execute(request).await))
+}
+
+pub async fn status<S>(
+    client: S,
+    url: String,
+    settings: settings::SettingsHandle,
+) -> Result<impl warp::Reply, warp::Rejection>
 where
     S: Status,
 {
@@ -311,6 +1100,7 @@ where
             let resp = StatusResponseBody {
                 bragi: BragiStatus {
                     version: VERSION.to_string(),
+                    query_settings_hash: settings.hash(),
                 },
                 mimir: MimirStatus {
                     version: res.version,
@@ -330,6 +1120,10 @@ where
     }
 }
 
+pub async fn openapi() -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(json(&*openapi::SPEC))
+}
+
 pub async fn metrics() -> Result<impl warp::Reply, warp::Rejection> {
     let reply = warp::reply::with_header(
         prometheus_handler::metrics(),
@@ -367,6 +1161,8 @@ pub fn build_es_indices_to_search(
                         indices.push(root_doctype(doc_type_str));
                     }
                 }
+                // coordinates are not indexed
+                Type::Coord => {}
                 Type::StopArea => {
                     // if some pt_dataset are specified
                     // we search for stops only in the corresponding es indices
@@ -412,6 +1208,138 @@ pub fn build_es_indices_to_search(
 mod tests {
     use super::*;
     use crate::adapters::primary::bragi::routes::forward_geocoder_get;
+    use crate::domain::ports::secondary::search::{self, Parameters, Search};
+
+    // A backend for which the prefix and the fuzzy queries find the given streets.
+    struct FakeSearch {
+        prefix: Vec<Street>,
+        fuzzy: Vec<Street>,
+    }
+
+    fn street(id: &str, label: &str) -> Street {
+        Street {
+            id: id.to_string(),
+            name: label.to_string(),
+            label: label.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Search for FakeSearch {
+        type Doc = serde_json::Value;
+
+        async fn search_documents(
+            &self,
+            _parameters: Parameters,
+        ) -> Result<Vec<Self::Doc>, search::Error> {
+            // Forward geocoding only uses multi search.
+            Ok(Vec::new())
+        }
+
+        async fn multi_search_documents(
+            &self,
+            parameters: Vec<Parameters>,
+        ) -> Result<Vec<Vec<Hit<Self::Doc>>>, search::Error> {
+            let hits = |streets: &[Street]| {
+                streets
+                    .iter()
+                    .take(parameters[0].result_limit as usize)
+                    .map(|street| Hit {
+                        document: serde_json::to_value(street).unwrap(),
+                        score: Some(1.0),
+                        explanation: None,
+                    })
+                    .collect()
+            };
+            Ok(vec![hits(&self.prefix), hits(&self.fuzzy)])
+        }
+    }
+
+    #[tokio::test]
+    async fn should_fill_the_response_after_collapsing_places() {
+        let client = FakeSearch {
+            prefix: vec![
+                street("a", "Rue de la Paix"),
+                street("b", "Rue de la Paix"),
+                street("c", "Rue de Rivoli"),
+                street("d", "Rue du Bac"),
+            ],
+            fuzzy: Vec::new(),
+        };
+        let mut settings = settings::QuerySettings::default();
+        settings.dedup.enabled = true;
+        settings.quotas.max_per_type.clear();
+        let params = ForwardGeocoderQuery {
+            q: "rue".to_string(),
+            limit: 3,
+            ..Default::default()
+        };
+        let places = forward_geocode_places(
+            &client,
+            params,
+            None,
+            &settings,
+            Duration::from_secs(1),
+            &[],
+        )
+        .await
+        .unwrap();
+        let ids: Vec<&str> = places.iter().map(|scored| scored.place.id()).collect();
+        assert_eq!(ids, ["a", "c", "d"]);
+        assert_eq!(places[0].collapsed_ids, ["b"]);
+    }
+
+    #[tokio::test]
+    async fn should_only_fall_back_on_free_text_for_structured_queries() {
+        let mut settings = settings::QuerySettings::default();
+        settings.merge_strategy = settings::MergeStrategy::Interleave;
+        settings.quotas.max_per_type.clear();
+        let params = || ForwardGeocoderQuery {
+            street: Some("rue de la paix".to_string()),
+            city: Some("paris".to_string()),
+            limit: 5,
+            ..Default::default()
+        };
+        let ids = |places: Vec<ScoredPlace>| -> Vec<String> {
+            places
+                .iter()
+                .map(|scored| scored.place.id().to_string())
+                .collect()
+        };
+
+        let client = FakeSearch {
+            prefix: vec![street("a", "Rue de la Paix (Paris)")],
+            fuzzy: vec![street("b", "Rue de la Paix (Lyon)")],
+        };
+        let places = forward_geocode_places(
+            &client,
+            params(),
+            None,
+            &settings,
+            Duration::from_secs(1),
+            &[],
+        )
+        .await
+        .unwrap();
+        assert_eq!(ids(places), ["a"], "places matching every component only");
+
+        let client = FakeSearch {
+            prefix: Vec::new(),
+            fuzzy: vec![street("b", "Rue de la Paix (Lyon)")],
+        };
+        let places = forward_geocode_places(
+            &client,
+            params(),
+            None,
+            &settings,
+            Duration::from_secs(1),
+            &[],
+        )
+        .await
+        .unwrap();
+        assert_eq!(ids(places), ["b"], "free text results when none matches");
+    }
 
     async fn indices_builder(query: &str) -> Vec<String> {
         let filter = forward_geocoder_get();
@@ -1,4 +1,7 @@
 pub mod api;
+pub mod gql;
 pub mod handlers;
+pub mod openapi;
 pub mod prometheus_handler;
+pub mod query_log;
 pub mod routes;
@@ -0,0 +1,532 @@
+//! OpenAPI 3 description of bragi's REST API.
+//!
+//! The schemas of the query parameters, request bodies and responses are generated
+//! from the types used by the routes and the handlers, so that the document follows
+//! their evolutions.
+
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::JsonSchema;
+use serde_json::{json, Map, Value as JsonValue};
+
+use crate::adapters::primary::bragi::api::{
+    AnalyzeQuery, BatchResponseBody, CodesQuery, FeaturesBatchQuery, FeaturesQuery,
+    FeaturesResponseBody, ForwardGeocoderExplainQuery, ForwardGeocoderQuery, JsonParam,
+    NearbyQuery, ReverseGeocoderQuery, StatusResponseBody, ZonesQuery,
+};
+use crate::adapters::primary::bragi::routes::{ApiError, InvalidRequestReason};
+use crate::adapters::primary::common::geocoding::GeocodeJsonResponse;
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Base path of the REST API.
+pub const PATH_PREFIX: &str = "/api/v1";
+
+lazy_static::lazy_static! {
+    /// The OpenAPI document, generated once.
+    pub static ref SPEC: JsonValue = spec();
+}
+
+/// Generates the OpenAPI document of the REST API.
+pub fn spec() -> JsonValue {
+    let mut gen = SchemaSettings::openapi3().into_generator();
+
+    let forward_parameters = query_parameters::<ForwardGeocoderQuery>(&mut gen);
+    let explain_parameters = query_parameters::<ForwardGeocoderExplainQuery>(&mut gen);
+    let analyze_parameters = query_parameters::<AnalyzeQuery>(&mut gen);
+    let reverse_parameters = query_parameters::<ReverseGeocoderQuery>(&mut gen);
+    let zones_parameters = query_parameters::<ZonesQuery>(&mut gen);
+    let nearby_parameters = query_parameters::<NearbyQuery>(&mut gen);
+    let mut codes_parameters = vec![
+        json!({
+            "name": "type",
+            "in": "path",
+            "required": true,
+            "description": "type of the code, like 'UIC' or 'ref:FR:SIRET'",
+            "schema": { "type": "string" }
+        }),
+        json!({
+            "name": "value",
+            "in": "path",
+            "required": true,
+            "description": "value of the code",
+            "schema": { "type": "string" }
+        }),
+    ];
+    codes_parameters.extend(query_parameters::<CodesQuery>(&mut gen));
+    let mut features_parameters = vec![json!({
+        "name": "id",
+        "in": "path",
+        "required": true,
+        "description": "id of the place",
+        "schema": { "type": "string" }
+    })];
+    features_parameters.extend(query_parameters::<FeaturesQuery>(&mut gen));
+    let features_batch_parameters = query_parameters::<FeaturesBatchQuery>(&mut gen);
+
+    let geocode_json = json_content(schema_for::<GeocodeJsonResponse>(&mut gen));
+    let shape = json_content(schema_for::<JsonParam>(&mut gen));
+    let batch = json!({
+        "application/json": {
+            "schema": { "type": "array", "items": schema_for::<ForwardGeocoderQuery>(&mut gen) }
+        },
+        "application/x-ndjson": {
+            "schema": { "type": "string", "description": "one JSON query per line" }
+        },
+        "text/csv": {
+            "schema": {
+                "type": "string",
+                "description": "one query per row, under a header row naming the query parameter of each column, like 'q', 'lat' or 'type[]'; empty cells are ignored"
+            }
+        }
+    });
+    let batch_response = json_content(schema_for::<BatchResponseBody>(&mut gen));
+    let features_batch_response = json_content(schema_for::<FeaturesResponseBody>(&mut gen));
+    let status_response = json_content(schema_for::<StatusResponseBody>(&mut gen));
+    let any_object = json_content(json!({ "type": "object" }));
+    let error = json_content(schema_for::<ApiError>(&mut gen));
+    // Not part of any response, but documents why a request is rejected.
+    schema_for::<InvalidRequestReason>(&mut gen);
+
+    let errors = json!({
+        "400": { "description": "The request is invalid, or could not be answered", "content": error },
+        "500": { "description": "Internal server error", "content": error }
+    });
+    let responses = |description: &str, content: &JsonValue| {
+        let mut responses = errors.clone();
+        responses["200"] = json!({ "description": description, "content": content });
+        responses
+    };
+
+    let mut paths = Map::new();
+    paths.insert(
+        path("autocomplete"),
+        json!({
+            "get": {
+                "summary": "Searches the places matching a query string",
+                "parameters": with_accept_language(&with_profile(&forward_parameters)),
+                "responses": responses("places, in geocodejson unless another format is requested", &geocode_json)
+            },
+            "post": {
+                "summary": "Searches the places matching a query string, within a shape",
+                "parameters": with_accept_language(&with_profile(&forward_parameters)),
+                "requestBody": { "required": true, "content": shape },
+                "responses": responses("places, in geocodejson unless another format is requested", &geocode_json)
+            }
+        }),
+    );
+    paths.insert(
+        path("autocomplete-batch"),
+        json!({
+            "post": {
+                "summary": "Searches the places matching each query of a batch",
+                "parameters": with_accept_language(&with_profile(&[])),
+                "requestBody": { "required": true, "content": batch },
+                "responses": responses("one result per query, in the order of the request", &batch_response)
+            }
+        }),
+    );
+    paths.insert(
+        path("autocomplete-explain"),
+        json!({
+            "get": {
+                "summary": "Explains how a document is scored by a forward geocoding query",
+                "parameters": with_accept_language(&with_profile(&explain_parameters)),
+                "responses": responses("Elasticsearch explanation", &any_object)
+            },
+            "post": {
+                "summary": "Explains how a document is scored by a forward geocoding query, within a shape",
+                "parameters": with_accept_language(&with_profile(&explain_parameters)),
+                "requestBody": { "required": true, "content": shape },
+                "responses": responses("Elasticsearch explanation", &any_object)
+            }
+        }),
+    );
+    paths.insert(
+        path("analyze"),
+        json!({
+            "get": {
+                "summary": "Analyzes a text with the Elasticsearch analyzers of some fields, when enabled in the settings",
+                "parameters": analyze_parameters,
+                "responses": responses("the tokens of the text, for each field", &any_object)
+            }
+        }),
+    );
+    paths.insert(
+        path("reverse"),
+        json!({
+            "get": {
+                "summary": "Searches the places around a location",
+                "parameters": with_accept_language(&with_profile(&reverse_parameters)),
+                "responses": responses("places, in geocodejson unless another format is requested", &geocode_json)
+            }
+        }),
+    );
+    paths.insert(
+        path("zones"),
+        json!({
+            "get": {
+                "summary": "Lists the administrative regions containing a location",
+                "parameters": with_accept_language(&zones_parameters),
+                "responses": responses("geocodejson admins, the most local first", &geocode_json)
+            }
+        }),
+    );
+    paths.insert(
+        path("nearby"),
+        json!({
+            "get": {
+                "summary": "Lists the pois and stops around a location, the closest first",
+                "parameters": with_accept_language(&nearby_parameters),
+                "responses": responses("places, in geocodejson unless another format is requested", &geocode_json)
+            }
+        }),
+    );
+    paths.insert(
+        path("codes/{type}/{value}"),
+        json!({
+            "get": {
+                "summary": "Retrieves the stops and admins having the given value for a code",
+                "parameters": with_accept_language(&codes_parameters),
+                "responses": responses("places, in geocodejson unless another format is requested", &geocode_json)
+            }
+        }),
+    );
+    paths.insert(
+        path("features/{id}"),
+        json!({
+            "get": {
+                "summary": "Retrieves the places with the given id",
+                "parameters": with_accept_language(&features_parameters),
+                "responses": responses("places, in geocodejson unless another format is requested", &geocode_json)
+            }
+        }),
+    );
+    paths.insert(
+        path("features"),
+        json!({
+            "get": {
+                "summary": "Retrieves several places by id",
+                "parameters": with_accept_language(&features_batch_parameters),
+                "responses": responses("one result per id, in the order of the request", &features_batch_response)
+            }
+        }),
+    );
+    paths.insert(
+        path("graphql"),
+        json!({
+            "get": {
+                "summary": "Runs a GraphQL query, given in the query parameters",
+                "parameters": with_accept_language(&with_profile(&[])),
+                "responses": responses("GraphQL response", &any_object)
+            },
+            "post": {
+                "summary": "Runs a GraphQL query",
+                "parameters": with_accept_language(&with_profile(&[])),
+                "requestBody": { "required": true, "content": any_object },
+                "responses": responses("GraphQL response", &any_object)
+            }
+        }),
+    );
+    paths.insert(
+        path("status"),
+        json!({
+            "get": {
+                "summary": "Versions of bragi and mimir, and status of Elasticsearch",
+                "responses": responses("status", &status_response)
+            }
+        }),
+    );
+    paths.insert(
+        path("metrics"),
+        json!({
+            "get": {
+                "summary": "Prometheus metrics",
+                "responses": {
+                    "200": {
+                        "description": "metrics",
+                        "content": { "text/plain": { "schema": { "type": "string" } } }
+                    }
+                }
+            }
+        }),
+    );
+    paths.insert(
+        path("openapi.json"),
+        json!({
+            "get": {
+                "summary": "This document",
+                "responses": responses("OpenAPI document", &any_object)
+            }
+        }),
+    );
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Bragi",
+            "description": "REST API of the Mimirsbrunn geocoder",
+            "version": VERSION
+        },
+        "paths": paths,
+        "components": {
+            "schemas": gen.take_definitions()
+        }
+    })
+}
+
+fn path(route: &str) -> String {
+    format!("{}/{}", PATH_PREFIX, route)
+}
+
+fn schema_for<T: JsonSchema>(gen: &mut SchemaGenerator) -> JsonValue {
+    serde_json::to_value(gen.subschema_for::<T>()).expect("schemas are serializable")
+}
+
+fn json_content(schema: JsonValue) -> JsonValue {
+    json!({ "application/json": { "schema": schema } })
+}
+
+// Describes each field of the query type `T` as a query parameter. Lists are
+// given as `name[]=a&name[]=b`, and maps as `name[key]=value`, the way serde_qs
+// expects them.
+fn query_parameters<T: JsonSchema>(gen: &mut SchemaGenerator) -> Vec<JsonValue> {
+    let root = serde_json::to_value(gen.root_schema_for::<T>()).expect("schemas are serializable");
+    let required: Vec<&str> = root["required"]
+        .as_array()
+        .map(|names| names.iter().filter_map(JsonValue::as_str).collect())
+        .unwrap_or_default();
+    root["properties"]
+        .as_object()
+        .map(|properties| {
+            properties
+                .iter()
+                .map(|(name, schema)| {
+                    let mut schema = schema.clone();
+                    let description = schema
+                        .as_object_mut()
+                        .and_then(|schema| schema.remove("description"));
+                    let parameter_name = if schema["type"] == "array" {
+                        format!("{}[]", name)
+                    } else {
+                        name.clone()
+                    };
+                    let mut parameter = json!({
+                        "name": parameter_name,
+                        "in": "query",
+                        "required": required.contains(&name.as_str()),
+                        "schema": schema
+                    });
+                    if let Some(description) = description {
+                        parameter["description"] = description;
+                    }
+                    if parameter["schema"]["type"] == "object" {
+                        parameter["style"] = json!("deepObject");
+                        parameter["explode"] = json!(true);
+                    }
+                    parameter
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// Adds the query settings profile, given either as a query parameter or as a header, to
+// the parameters of the endpoints searching places.
+fn with_profile(parameters: &[JsonValue]) -> Vec<JsonValue> {
+    let mut parameters = parameters.to_vec();
+    parameters.push(json!({
+        "name": "profile",
+        "in": "query",
+        "required": false,
+        "description": "name of the query settings profile, the default profile if not given",
+        "schema": { "type": "string" }
+    }));
+    parameters.push(json!({
+        "name": "X-Bragi-Profile",
+        "in": "header",
+        "required": false,
+        "description": "name of the query settings profile, when the 'profile' parameter is not given",
+        "schema": { "type": "string" }
+    }));
+    parameters
+}
+
+// Adds the Accept-Language header to the parameters of the endpoints returning places.
+fn with_accept_language(parameters: &[JsonValue]) -> Vec<JsonValue> {
+    let mut parameters = parameters.to_vec();
+    parameters.push(json!({
+        "name": "Accept-Language",
+        "in": "header",
+        "required": false,
+        "description": "preferred languages of the names and labels, after the 'lang' parameter",
+        "schema": { "type": "string" }
+    }));
+    parameters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn should_describe_all_the_routes() {
+        // The first path segment of each route defined in routes.rs
+        let re = Regex::new(r#"warp::path!?\("([^"]+)""#).unwrap();
+        let routes: BTreeSet<String> = re
+            .captures_iter(include_str!("routes.rs"))
+            .map(|captures| captures[1].to_string())
+            .collect();
+        let spec = spec();
+        let documented: BTreeSet<String> = spec["paths"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(|path| {
+                path.trim_start_matches(PATH_PREFIX)
+                    .trim_start_matches('/')
+                    .split('/')
+                    .next()
+                    .unwrap_or_default()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(
+            routes, documented,
+            "the routes in routes.rs and the OpenAPI document have drifted apart"
+        );
+    }
+
+    #[test]
+    fn should_document_the_query_parameters_of_each_route() {
+        fn names(parameters: &[JsonValue]) -> BTreeSet<String> {
+            parameters
+                .iter()
+                .filter(|parameter| parameter["in"] == "query")
+                .filter_map(|parameter| parameter["name"].as_str())
+                .map(str::to_string)
+                .collect()
+        }
+
+        let mut gen = SchemaSettings::openapi3().into_generator();
+        let forward = names(&with_profile(&query_parameters::<ForwardGeocoderQuery>(
+            &mut gen,
+        )));
+        let explain = names(&with_profile(&query_parameters::<
+            ForwardGeocoderExplainQuery,
+        >(&mut gen)));
+        // Only the profile, for the routes without query type
+        let profile = names(&with_profile(&[]));
+        // The query parameters deserialized by each route of routes.rs
+        let routes = [
+            ("autocomplete", "get", forward.clone()),
+            ("autocomplete", "post", forward),
+            ("autocomplete-batch", "post", profile.clone()),
+            ("autocomplete-explain", "get", explain.clone()),
+            ("autocomplete-explain", "post", explain),
+            (
+                "analyze",
+                "get",
+                names(&query_parameters::<AnalyzeQuery>(&mut gen)),
+            ),
+            (
+                "reverse",
+                "get",
+                names(&with_profile(&query_parameters::<ReverseGeocoderQuery>(
+                    &mut gen,
+                ))),
+            ),
+            (
+                "zones",
+                "get",
+                names(&query_parameters::<ZonesQuery>(&mut gen)),
+            ),
+            (
+                "nearby",
+                "get",
+                names(&query_parameters::<NearbyQuery>(&mut gen)),
+            ),
+            (
+                "codes/{type}/{value}",
+                "get",
+                names(&query_parameters::<CodesQuery>(&mut gen)),
+            ),
+            (
+                "features/{id}",
+                "get",
+                names(&query_parameters::<FeaturesQuery>(&mut gen)),
+            ),
+            (
+                "features",
+                "get",
+                names(&query_parameters::<FeaturesBatchQuery>(&mut gen)),
+            ),
+            ("graphql", "get", profile.clone()),
+            ("graphql", "post", profile),
+            ("status", "get", BTreeSet::new()),
+            ("metrics", "get", BTreeSet::new()),
+            ("openapi.json", "get", BTreeSet::new()),
+        ];
+
+        let spec = spec();
+        let documented: BTreeSet<(String, String)> = spec["paths"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .flat_map(|(path, operations)| {
+                operations
+                    .as_object()
+                    .unwrap()
+                    .keys()
+                    .map(move |method| (path.clone(), method.clone()))
+            })
+            .collect();
+        let described: BTreeSet<(String, String)> = routes
+            .iter()
+            .map(|(route, method, _)| (path(route), method.to_string()))
+            .collect();
+        assert_eq!(
+            documented, described,
+            "the operations of the OpenAPI document are not all checked"
+        );
+
+        for (route, method, expected) in routes {
+            let parameters = spec["paths"][path(route).as_str()][method]["parameters"]
+                .as_array()
+                .cloned()
+                .unwrap_or_default();
+            assert_eq!(
+                names(&parameters),
+                expected,
+                "the query parameters of {} {} are not documented",
+                method,
+                route
+            );
+        }
+    }
+
+    #[test]
+    fn should_generate_query_parameters_and_schemas() {
+        let spec = spec();
+        let parameters = spec["paths"]["/api/v1/autocomplete"]["get"]["parameters"]
+            .as_array()
+            .unwrap();
+        let names: Vec<&str> = parameters
+            .iter()
+            .filter_map(|parameter| parameter["name"].as_str())
+            .collect();
+        assert!(names.contains(&"q"));
+        assert!(names.contains(&"type[]"));
+        assert!(names.contains(&"proximity_scale"));
+        let schemas = spec["components"]["schemas"].as_object().unwrap();
+        for schema in [
+            "GeocodeJsonResponse",
+            "Type",
+            "ApiError",
+            "InvalidRequestReason",
+        ] {
+            assert!(schemas.contains_key(schema), "missing schema {}", schema);
+        }
+    }
+}
@@ -10,8 +10,15 @@ lazy_static::lazy_static! {
         map.insert("/api/v1/metrics", "metrics");
         map.insert("/api/v1/status", "status");
         map.insert("/api/v1/reverse", "reverse");
+        map.insert("/api/v1/zones", "zones");
+        map.insert("/api/v1/nearby", "nearby");
         map.insert("/api/v1/autocomplete", "autocomplete");
         map.insert("/api/v1/autocomplete-explain", "autocomplete-explain");
+        map.insert("/api/v1/autocomplete-batch", "autocomplete-batch");
+        map.insert("/api/v1/analyze", "analyze");
+        map.insert("/api/v1/graphql", "graphql");
+        map.insert("/api/v1/features", "features-batch");
+        map.insert("/api/v1/openapi.json", "openapi");
         map
     };
 
@@ -28,6 +35,8 @@ fn get_ressource_name(path: &str) -> String {
         .unwrap_or_else(|| {
             if path.starts_with("/api/v1/features") {
                 &FEATURES_ROUTE
+            } else if path.starts_with("/api/v1/codes") {
+                "codes"
             } else {
                 ""
             }
@@ -57,6 +66,21 @@ lazy_static::lazy_static! {
         "current number of http request being served"
     )
     .unwrap();
+
+    static ref PROFILE_REQ_HISTOGRAM: prometheus::HistogramVec = prometheus::register_histogram_vec!(
+        "bragi_profile_request_duration_seconds",
+        "The latencies of the queries in seconds, by query settings profile.",
+        &["handler", "profile"],
+        prometheus::exponential_buckets(0.001, 1.5, 25).unwrap()
+    )
+    .unwrap();
+
+    static ref PROFILE_EMPTY_COUNTER: prometheus::CounterVec = prometheus::register_counter_vec!(
+        "bragi_profile_empty_responses_total",
+        "Total number of queries without any result, by query settings profile.",
+        &["handler", "profile"]
+    )
+    .unwrap();
 }
 
 #[cfg(feature = "metrics")]
@@ -86,6 +110,29 @@ pub fn update_metrics(info: warp::log::Info) {
 #[cfg(not(feature = "prometheus"))]
 pub fn update_metrics(_info: warp::log::Info) {}
 
+/// Records a query served with the settings of a profile, to compare the profiles.
+/// The number of queries of each profile is the count of the latency histogram.
+#[cfg(feature = "metrics")]
+pub fn observe_profile(handler: &str, profile: &str, elapsed: std::time::Duration, empty: bool) {
+    PROFILE_REQ_HISTOGRAM
+        .with_label_values(&[handler, profile])
+        .observe(elapsed.as_secs_f64());
+    // The counter is created for every profile, so that its rate is 0 rather than missing.
+    let empty_counter = PROFILE_EMPTY_COUNTER.with_label_values(&[handler, profile]);
+    if empty {
+        empty_counter.inc();
+    }
+}
+
+#[cfg(not(feature = "metrics"))]
+pub fn observe_profile(
+    _handler: &str,
+    _profile: &str,
+    _elapsed: std::time::Duration,
+    _empty: bool,
+) {
+}
+
 #[cfg(feature = "metrics")]
 pub fn metrics() -> String {
     let mut buffer = vec![];
@@ -0,0 +1,246 @@
+//! Structured log of the autocomplete queries, to evaluate relevance changes on real traffic.
+//!
+//! Each GET request to the autocomplete endpoint is appended to the log as a line of JSON
+//! (NDJSON), with its parameters, its profile, its latency and the ids of the places it
+//! returned. The `replay` binary replays such a log, and compares the ids it gets with
+//! those of the log.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use serde_qs::Config;
+use std::convert::Infallible;
+use std::fs::OpenOptions;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::time::Instant;
+use tracing::warn;
+use warp::http::{HeaderMap, Method};
+use warp::hyper::{body, Body};
+use warp::path::FullPath;
+use warp::reply::Response;
+use warp::{Filter, Reply};
+
+use crate::adapters::primary::bragi::api::ForwardGeocoderQuery;
+use crate::adapters::primary::bragi::openapi::PATH_PREFIX;
+use crate::adapters::primary::bragi::routes::PROFILE_HEADER;
+use crate::adapters::primary::common::settings::DEFAULT_PROFILE;
+
+/// Number of entries waiting to be written, beyond which new entries are dropped.
+const QUERY_LOG_CAPACITY: usize = 4096;
+
+/// A request to the autocomplete endpoint, as recorded in the query log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryLogEntry {
+    /// Time at which the request was received.
+    pub timestamp: DateTime<Utc>,
+    /// Query string of the request.
+    pub params: String,
+    /// Query settings profile of the request.
+    pub profile: String,
+    /// HTTP status of the response.
+    pub status: u16,
+    /// Time taken to answer the request, in milliseconds.
+    pub latency_ms: f64,
+    /// Ids of the places returned, in order.
+    pub ids: Vec<String>,
+}
+
+impl QueryLogEntry {
+    /// Parses the parameters of the request, the same way as the autocomplete endpoint.
+    pub fn forward_geocoder_query(&self) -> Result<ForwardGeocoderQuery, serde_qs::Error> {
+        Config::new(2, false).deserialize_str(&self.params)
+    }
+}
+
+/// Appends entries to a query log file. The entries are written by a dedicated thread,
+/// so that requests are never blocked by the log: when the thread lags too far behind,
+/// entries are dropped.
+#[derive(Debug, Clone)]
+pub struct QueryLog {
+    sender: SyncSender<QueryLogEntry>,
+}
+
+impl QueryLog {
+    pub fn create(path: &Path) -> Result<Self, io::Error> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let (sender, receiver) = sync_channel::<QueryLogEntry>(QUERY_LOG_CAPACITY);
+        std::thread::spawn(move || {
+            let mut writer = BufWriter::new(file);
+            while let Ok(entry) = receiver.recv() {
+                // Flushes once all the pending entries are written.
+                let written = std::iter::once(entry)
+                    .chain(receiver.try_iter())
+                    .try_for_each(|entry| {
+                        serde_json::to_writer(&mut writer, &entry)?;
+                        writer.write_all(b"\n")
+                    })
+                    .and_then(|_| writer.flush());
+                if let Err(err) = written {
+                    warn!("Cannot write the query log: {}", err);
+                }
+            }
+        });
+        Ok(QueryLog { sender })
+    }
+
+    pub fn record(&self, entry: QueryLogEntry) {
+        if let Err(err) = self.sender.try_send(entry) {
+            warn!("Dropping a query log entry: {}", err);
+        }
+    }
+}
+
+/// Records the GET requests to the autocomplete endpoint in the query log, if there is one.
+pub fn query_log_filter<F, T>(
+    filter: F,
+    query_log: Option<QueryLog>,
+) -> impl Filter<Extract = (Response,), Error = Infallible> + Clone + Send + Sync
+where
+    F: Filter<Extract = (T,), Error = Infallible> + Clone + Send + Sync,
+    F::Extract: Reply,
+    T: Reply,
+{
+    warp::method()
+        .and(warp::path::full())
+        .and(warp::query::raw().or(warp::any().map(String::new)).unify())
+        .and(warp::header::headers_cloned())
+        .and(warp::any().map(|| (Utc::now(), Instant::now())))
+        .and(filter)
+        .and_then(
+            move |method: Method,
+                  path: FullPath,
+                  params: String,
+                  headers: HeaderMap,
+                  (timestamp, start): (DateTime<Utc>, Instant),
+                  reply: T| {
+                let query_log = query_log.clone();
+                async move {
+                    let response = reply.into_response();
+                    let autocomplete = format!("{}/autocomplete", PATH_PREFIX);
+                    let response = match query_log {
+                        Some(query_log)
+                            if method == Method::GET && path.as_str() == autocomplete =>
+                        {
+                            let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+                            let profile = request_profile(&params, &headers);
+                            let (parts, content) = response.into_parts();
+                            let content = body::to_bytes(content).await.unwrap_or_else(|err| {
+                                warn!("Cannot read the response to log it: {}", err);
+                                Default::default()
+                            });
+                            let ids = serde_json::from_slice(&content)
+                                .map(|json| returned_ids(&json))
+                                .unwrap_or_default();
+                            query_log.record(QueryLogEntry {
+                                timestamp,
+                                params,
+                                profile,
+                                status: parts.status.as_u16(),
+                                latency_ms,
+                                ids,
+                            });
+                            Response::from_parts(parts, Body::from(content))
+                        }
+                        _ => response,
+                    };
+                    Ok::<_, Infallible>(response)
+                }
+            },
+        )
+}
+
+// The profile is selected by the query parameter first, as done by `routes::with_profile`.
+fn request_profile(params: &str, headers: &HeaderMap) -> String {
+    url::form_urlencoded::parse(params.as_bytes())
+        .find(|(key, _)| key == "profile")
+        .map(|(_, profile)| profile.into_owned())
+        .or_else(|| {
+            headers
+                .get(PROFILE_HEADER)
+                .and_then(|profile| profile.to_str().ok())
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+}
+
+/// Returns the ids of the places of an autocomplete response, in any output format.
+pub fn returned_ids(json: &JsonValue) -> Vec<String> {
+    match json {
+        // Nominatim formats
+        JsonValue::Array(places) => places
+            .iter()
+            .filter_map(|place| place["place_id"].as_str())
+            .map(str::to_string)
+            .collect(),
+        // GeocodeJSON and Pelias
+        _ => json["features"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|feature| {
+                let properties = &feature["properties"];
+                properties["geocoding"]["id"]
+                    .as_str()
+                    .or_else(|| properties["id"].as_str())
+            })
+            .map(str::to_string)
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn should_find_the_returned_ids_in_all_formats() {
+        let geocode_json = json!({
+            "type": "FeatureCollection",
+            "features": [
+                { "properties": { "geocoding": { "id": "admin:osm:relation:7444" } } },
+                { "properties": { "geocoding": { "id": "stop_area:SA:1" } } }
+            ]
+        });
+        assert_eq!(
+            returned_ids(&geocode_json),
+            ["admin:osm:relation:7444", "stop_area:SA:1"]
+        );
+        let pelias = json!({ "features": [ { "properties": { "id": "poi:1" } } ] });
+        assert_eq!(returned_ids(&pelias), ["poi:1"]);
+        let nominatim = json!([ { "place_id": "addr:2.3;48.8:12" } ]);
+        assert_eq!(returned_ids(&nominatim), ["addr:2.3;48.8:12"]);
+        assert!(returned_ids(&json!({ "short": "bad request" })).is_empty());
+    }
+
+    #[test]
+    fn should_select_the_profile_of_the_request() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(request_profile("q=paris", &headers), DEFAULT_PROFILE);
+        headers.insert(PROFILE_HEADER, "stops_first".parse().unwrap());
+        assert_eq!(request_profile("q=paris", &headers), "stops_first");
+        assert_eq!(
+            request_profile("q=paris&profile=pois_first", &headers),
+            "pois_first"
+        );
+    }
+
+    #[test]
+    fn should_parse_the_logged_parameters() {
+        let entry = QueryLogEntry {
+            timestamp: Utc::now(),
+            params: String::from("q=rue%20de%20la%20paix&type[]=street&limit=5"),
+            profile: DEFAULT_PROFILE.to_string(),
+            status: 200,
+            latency_ms: 12.5,
+            ids: vec![],
+        };
+        let line = serde_json::to_string(&entry).unwrap();
+        let entry: QueryLogEntry = serde_json::from_str(&line).unwrap();
+        let query = entry.forward_geocoder_query().unwrap();
+        assert_eq!(query.q, "rue de la paix");
+        assert_eq!(query.limit, 5);
+    }
+}
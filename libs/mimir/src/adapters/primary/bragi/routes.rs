@@ -1,20 +1,30 @@
 use crate::adapters::primary::bragi::api::{
-    FeaturesQuery, ForwardGeocoderExplainQuery, ForwardGeocoderQuery, JsonParam,
-    ReverseGeocoderQuery, Type,
+    AnalyzeQuery, CodesQuery, FeaturesBatchQuery, FeaturesQuery, ForwardGeocoderExplainQuery,
+    ForwardGeocoderQuery, JsonParam, NearbyQuery, ReverseGeocoderQuery, Type, ZonesQuery,
 };
+use crate::adapters::primary::bragi::gql::BragiSchema;
 use crate::adapters::primary::bragi::handlers::{InternalError, InternalErrorReason};
-use crate::adapters::primary::common::settings::QuerySettings;
+use crate::adapters::primary::common::settings::{Profile, SettingsHandle};
 use crate::domain::ports::primary::search_documents::SearchDocuments;
+use async_graphql_warp::GraphQLBadRequest;
 use geojson::{GeoJson, Geometry};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_qs::Config;
 use std::convert::Infallible;
 use std::time::Duration;
 use tracing::instrument;
 use url::Url;
+use warp::hyper::body::Bytes;
 use warp::reject::MethodNotAllowed;
 use warp::{http::StatusCode, path, reject::Reject, Filter, Rejection, Reply};
 
+/// Upper limit on the size of the body of a batch request (in bytes)
+pub const BATCH_CONTENT_LENGTH_LIMIT: u64 = 1024 * 1024 * 16;
+
+/// Header selecting the query settings profile of a request.
+pub const PROFILE_HEADER: &str = "x-bragi-profile";
+
 /// This function defines the base path for Bragi's REST API
 fn path_prefix() -> impl Filter<Extract = (), Error = Rejection> + Clone {
     path!("api" / "v1" / ..).boxed()
@@ -61,6 +71,35 @@ pub fn forward_geocoder_post(
         .and(forward_geocoder_body()) // Shape
 }
 
+/// This is the entry warp filter for the POST batch autocomplete endpoint
+///
+/// It validates:
+/// * It is a POST HTTP request
+/// * The path is <prefix> / autocomplete-batch
+/// * The body is either a JSON array of queries, or, if the content type is
+///   'application/x-ndjson', one JSON query per line, or, if the content type is
+///   'text/csv', one query per row, under a header row of query parameter names.
+/// * The batch does not contain more than `max_items` queries.
+///
+/// Each query in the batch is validated individually, so that a single invalid
+/// query does not reject the whole batch.
+#[instrument]
+pub fn forward_geocoder_batch(
+    max_items: usize,
+) -> impl Filter<Extract = (Vec<Result<ForwardGeocoderQuery, ApiError>>,), Error = Rejection> + Clone
+{
+    warp::post()
+        .and(path_prefix())
+        .and(warp::path("autocomplete-batch"))
+        .and(warp::path::end())
+        .and(warp::header::optional::<String>("content-type"))
+        .and(warp::body::content_length_limit(BATCH_CONTENT_LENGTH_LIMIT))
+        .and(warp::body::bytes())
+        .and_then(move |content_type: Option<String>, body: Bytes| {
+            forward_geocoder_batch_body(content_type, body, max_items)
+        })
+}
+
 #[instrument]
 pub fn forward_geocoder_explain_get(
 ) -> impl Filter<Extract = (ForwardGeocoderExplainQuery, Option<Geometry>), Error = Rejection> + Clone
@@ -85,15 +124,75 @@ pub fn forward_geocoder_explain_post(
         .and(forward_geocoder_body()) // Shape
 }
 
+/// This is the entry warp filter analyzing a text as Elasticsearch does.
+///
+/// It validates:
+/// * It is a GET HTTP request
+/// * The path is <prefix> / analyze
+/// * The route is enabled, otherwise it is not found: it exposes how the documents are
+///   indexed, so it is meant for debugging.
+/// * It has valid query parameters, with a non empty text.
+#[instrument]
+pub fn analyze(enabled: bool) -> impl Filter<Extract = (AnalyzeQuery,), Error = Rejection> + Clone {
+    warp::get()
+        .and(path_prefix())
+        .and(warp::path("analyze"))
+        .and(warp::path::end())
+        .and_then(move || async move {
+            if enabled {
+                Ok(())
+            } else {
+                Err(warp::reject::not_found())
+            }
+        })
+        .untuple_one()
+        .and(analyze_query())
+}
+
 /// This function reads the input parameters on a get request, makes a summary validation
-/// of the parameters, and returns them.
+/// of the parameters, with a radius of at most `max_radius` meters, and returns them.
 #[instrument]
 pub fn reverse_geocoder(
+    max_radius: u32,
 ) -> impl Filter<Extract = (ReverseGeocoderQuery,), Error = Rejection> + Clone {
     warp::get()
         .and(path_prefix())
         .and(warp::path("reverse"))
         .and(reverse_geocoder_query())
+        .and_then(move |params: ReverseGeocoderQuery| ensure_radius_in_range(params, max_radius))
+}
+
+/// This function reads the input parameters on a get request for the administrative
+/// regions containing a location, makes a summary validation of the parameters, and
+/// returns them.
+#[instrument]
+pub fn zones() -> impl Filter<Extract = (ZonesQuery,), Error = Rejection> + Clone {
+    warp::get()
+        .and(path_prefix())
+        .and(warp::path("zones"))
+        .and(warp::path::end())
+        .and(zones_query())
+}
+
+/// This is the entry warp filter looking for the pois and stops around a location.
+///
+/// It validates:
+/// * It is a GET HTTP request
+/// * The path is <prefix> / nearby
+/// * It has valid query parameters, with a valid location, and only the types 'poi'
+///   and 'public_transport:stop_area'.
+/// * The radius is at most `max_radius` meters, and the limit between 1 and `max_limit`.
+#[instrument]
+pub fn nearby(
+    max_radius: u32,
+    max_limit: i64,
+) -> impl Filter<Extract = (NearbyQuery,), Error = Rejection> + Clone {
+    warp::get()
+        .and(path_prefix())
+        .and(warp::path("nearby"))
+        .and(warp::path::end())
+        .and(nearby_query())
+        .and_then(move |params: NearbyQuery| ensure_nearby_in_range(params, max_radius, max_limit))
 }
 
 /// This function reads the input parameters on a get request, makes a summary validation
@@ -106,6 +205,74 @@ pub fn features() -> impl Filter<Extract = (String, FeaturesQuery), Error = Reje
         .and(features_query())
 }
 
+/// This is the entry warp filter looking for the places having a given code.
+///
+/// It validates:
+/// * It is a GET HTTP request
+/// * The path is <prefix> / codes / <code type> / <code value>, with a valid code type
+/// * It has valid query parameters, with only the types 'public_transport:stop_area', 'zone'
+///   and 'city'.
+#[instrument]
+pub fn codes() -> impl Filter<Extract = (String, String, CodesQuery), Error = Rejection> + Clone {
+    warp::get()
+        .and(path_prefix())
+        .and(warp::path!("codes" / String / String))
+        .and_then(|code_type: String, value: String| async move {
+            ensure_field_key_valid(&code_type).map(|_| (code_type, value))
+        })
+        .untuple_one()
+        .and(codes_query())
+}
+
+/// This is the entry warp filter looking for several places by id.
+///
+/// It validates:
+/// * It is a GET HTTP request
+/// * The path is <prefix> / features
+/// * It has valid query parameters, with between 1 and `max_ids` ids.
+#[instrument]
+pub fn features_batch(
+    max_ids: usize,
+) -> impl Filter<Extract = (FeaturesBatchQuery,), Error = Rejection> + Clone {
+    warp::get()
+        .and(path_prefix())
+        .and(warp::path("features"))
+        .and(warp::path::end())
+        .and(warp::filters::query::raw())
+        .and_then(move |param: String| features_batch_query(param, max_ids))
+}
+
+pub async fn features_batch_query(
+    param: String,
+    max_ids: usize,
+) -> Result<FeaturesBatchQuery, Rejection> {
+    let config = Config::new(2, false);
+    let params: FeaturesBatchQuery = config.deserialize_str(&param).map_err(|err| {
+        warp::reject::custom(InvalidRequest {
+            reason: InvalidRequestReason::CannotDeserialize,
+            info: err.to_string(),
+        })
+    })?;
+    validate_tolerance(params.simplify)?;
+    let count = params.ids.as_ref().map_or(0, Vec::len);
+    if count == 0 {
+        Err(warp::reject::custom(InvalidRequest {
+            reason: InvalidRequestReason::EmptyQueryString,
+            info: "You must provide at least one id".to_string(),
+        }))
+    } else if count > max_ids {
+        Err(warp::reject::custom(InvalidRequest {
+            reason: InvalidRequestReason::BatchTooLarge,
+            info: format!(
+                "the request contains {} ids, the maximum is {}",
+                count, max_ids
+            ),
+        }))
+    } else {
+        Ok(params)
+    }
+}
+
 pub fn with_client<S>(s: S) -> impl Filter<Extract = (S,), Error = std::convert::Infallible> + Clone
 where
     S: SearchDocuments + Send + Sync + Clone,
@@ -113,9 +280,37 @@ where
     warp::any().map(move || s.clone())
 }
 
-pub fn with_settings(
-    settings: QuerySettings,
-) -> impl Filter<Extract = (QuerySettings,), Error = std::convert::Infallible> + Clone {
+/// Extracts the query settings of the profile requested with the `profile` query parameter,
+/// or with the `X-Bragi-Profile` header, or the default query settings. Each request is
+/// served with the settings active when it was received.
+pub fn with_profile(
+    settings: SettingsHandle,
+) -> impl Filter<Extract = (Profile,), Error = Rejection> + Clone {
+    warp::header::optional::<String>(PROFILE_HEADER)
+        .and(warp::query::raw().or(warp::any().map(String::new)).unify())
+        .and_then(move |header: Option<String>, query: String| {
+            let settings = settings.clone();
+            async move {
+                let name = url::form_urlencoded::parse(query.as_bytes())
+                    .find(|(key, _)| key == "profile")
+                    .map(|(_, name)| name.into_owned())
+                    .or(header);
+                settings.profile(name.as_deref()).ok_or_else(|| {
+                    warp::reject::custom(InvalidRequest {
+                        reason: InvalidRequestReason::UnknownProfile,
+                        info: format!(
+                            "unknown query settings profile '{}'",
+                            name.unwrap_or_default()
+                        ),
+                    })
+                })
+            }
+        })
+}
+
+pub fn with_settings_handle(
+    settings: SettingsHandle,
+) -> impl Filter<Extract = (SettingsHandle,), Error = std::convert::Infallible> + Clone {
     warp::any().map(move || settings.clone())
 }
 
@@ -125,6 +320,80 @@ pub fn with_timeout(
     warp::any().map(move || timeout)
 }
 
+pub fn with_concurrency(
+    concurrency: usize,
+) -> impl Filter<Extract = (usize,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || concurrency)
+}
+
+/// Extracts the languages the client prefers, from its Accept-Language header,
+/// followed by the `fallback` languages.
+pub fn with_langs(
+    fallback: Vec<String>,
+) -> impl Filter<Extract = (Vec<String>,), Error = std::convert::Infallible> + Clone {
+    warp::header::optional::<String>("accept-language")
+        .or(warp::any().map(|| None))
+        .unify()
+        .map(move |header: Option<String>| {
+            let mut langs = header
+                .as_deref()
+                .map(parse_accept_language)
+                .unwrap_or_default();
+            for lang in &fallback {
+                if !langs.contains(lang) {
+                    langs.push(lang.clone());
+                }
+            }
+            langs
+        })
+}
+
+/// Parses the value of an Accept-Language header, and returns the languages
+/// by decreasing quality.
+///
+/// Languages with a quality of 0 and the wildcard are ignored. A regional tag,
+/// like `fr-CH`, is followed by its primary language, `fr`, unless the client
+/// already listed it.
+pub fn parse_accept_language(header: &str) -> Vec<String> {
+    let mut tags: Vec<(String, f32)> = header
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let tag = parts.next()?.trim().to_lowercase();
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+            if tag.is_empty() || tag == "*" || quality <= 0.0 {
+                None
+            } else {
+                Some((tag, quality))
+            }
+        })
+        .collect();
+    // The sort is stable, so tags with the same quality keep the client's order.
+    tags.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut langs: Vec<String> = Vec::new();
+    for (tag, _) in &tags {
+        if !langs.contains(tag) {
+            langs.push(tag.clone());
+        }
+    }
+    let mut expanded: Vec<String> = Vec::with_capacity(langs.len());
+    for lang in &langs {
+        if !expanded.contains(lang) {
+            expanded.push(lang.clone());
+        }
+        if let Some((primary, _)) = lang.split_once('-') {
+            let primary = primary.to_string();
+            if !langs.contains(&primary) && !expanded.contains(&primary) {
+                expanded.push(primary);
+            }
+        }
+    }
+    expanded
+}
+
 pub fn with_elasticsearch(
     url: &Url, // elasticsearch url
 ) -> impl Filter<Extract = (String,), Error = std::convert::Infallible> + Clone {
@@ -132,13 +401,14 @@ pub fn with_elasticsearch(
     warp::any().map(move || url.clone())
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+/// The body of the responses to requests which could not be answered.
+#[derive(Deserialize, Serialize, Debug, JsonSchema)]
 pub struct ApiError {
     pub short: String,
     pub long: String,
 }
 
-#[derive(Deserialize, Serialize, Debug, PartialEq)]
+#[derive(Deserialize, Serialize, Debug, PartialEq, JsonSchema)]
 pub enum InvalidRequestReason {
     CannotDeserialize,
     EmptyQueryString,
@@ -146,6 +416,15 @@ pub enum InvalidRequestReason {
     InconsistentZoneRequest,
     InconsistentLatLonRequest,
     OutOfRangeLatLonRequest,
+    BatchTooLarge,
+    NegativeTolerance,
+    OutOfRangeConfidence,
+    OutOfRangeRadius,
+    OutOfRangeLimit,
+    UnknownProfile,
+    InconsistentNearbyRequest,
+    InconsistentCodesRequest,
+    InvalidFieldKey,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -180,33 +459,65 @@ pub fn forward_geocoder_query(
         .and_then(ensure_query_string_not_empty)
         .and_then(ensure_zone_type_consistent)
         .and_then(ensure_lat_lon_consistent)
+        .and_then(ensure_min_confidence_in_range)
+        .and_then(ensure_poi_properties_valid)
 }
 
 /// Extract and Validate input parameters from the query
 #[instrument]
 pub fn forward_geocoder_explain_query(
 ) -> impl Filter<Extract = (ForwardGeocoderExplainQuery,), Error = Rejection> + Copy {
-    warp::filters::query::raw().and_then(|param: String| async move {
-        // max_depth=1:
-        // for more informations: https://docs.rs/serde_qs/latest/serde_qs/index.html
-        let config = Config::new(2, false);
-        tracing::info!("forward_geocoder_explain query : {}", param);
-        config.deserialize_str(&param).map_err(|err| {
-            warp::reject::custom(InvalidRequest {
-                reason: InvalidRequestReason::CannotDeserialize,
-                info: err.to_string(),
+    warp::filters::query::raw()
+        .and_then(|param: String| async move {
+            // max_depth=1:
+            // for more informations: https://docs.rs/serde_qs/latest/serde_qs/index.html
+            let config = Config::new(2, false);
+            tracing::info!("forward_geocoder_explain query : {}", param);
+            config.deserialize_str(&param).map_err(|err| {
+                warp::reject::custom(InvalidRequest {
+                    reason: InvalidRequestReason::CannotDeserialize,
+                    info: err.to_string(),
+                })
             })
         })
-    })
+        .and_then(|mut params: ForwardGeocoderExplainQuery| async move {
+            params.query = ensure_poi_properties_valid(params.query).await?;
+            Ok::<_, Rejection>(params)
+        })
+}
+
+pub fn analyze_query() -> impl Filter<Extract = (AnalyzeQuery,), Error = Rejection> + Copy {
+    warp::filters::query::raw()
+        .and_then(|param: String| async move {
+            let config = Config::new(2, false);
+            tracing::info!("Analyze query : {}", param);
+            config.deserialize_str(&param).map_err(|err| {
+                warp::reject::custom(InvalidRequest {
+                    reason: InvalidRequestReason::CannotDeserialize,
+                    info: err.to_string(),
+                })
+            })
+        })
+        .and_then(|params: AnalyzeQuery| async move {
+            if params.q.is_empty() {
+                Err(warp::reject::custom(InvalidRequest {
+                    reason: InvalidRequestReason::EmptyQueryString,
+                    info: "You must provide a non-empty text to analyze".to_string(),
+                }))
+            } else {
+                Ok(params)
+            }
+        })
 }
 
 pub async fn ensure_query_string_not_empty(
     params: ForwardGeocoderQuery,
 ) -> Result<ForwardGeocoderQuery, Rejection> {
-    if params.q.is_empty() {
+    if params.q.is_empty() && params.structured_address().is_none() {
         Err(warp::reject::custom(InvalidRequest {
             reason: InvalidRequestReason::EmptyQueryString,
-            info: "You must provide and non-empty query string".to_string(),
+            info: "You must provide and non-empty query string, or an address component"
+                .to_string(),
         }))
     } else {
         Ok(params)
@@ -243,6 +554,24 @@ pub async fn ensure_lat_lon_consistent(
     }
 }
 
+/// This filter ensures that the minimum confidence, if any, is in [0, 1].
+pub async fn ensure_min_confidence_in_range(
+    params: ForwardGeocoderQuery,
+) -> Result<ForwardGeocoderQuery, Rejection> {
+    match params.min_confidence {
+        Some(min_confidence) if !(0f64..=1f64).contains(&min_confidence) => {
+            Err(warp::reject::custom(InvalidRequest {
+                reason: InvalidRequestReason::OutOfRangeConfidence,
+                info: format!(
+                    "requested minimum confidence {} is outside of range [0;1]",
+                    min_confidence
+                ),
+            }))
+        }
+        _ => Ok(params),
+    }
+}
+
 /// This filter ensures that if the user requests 'zone', then he must specify the list
 /// of zone_types.
 pub async fn ensure_zone_type_consistent(
@@ -269,6 +598,158 @@ pub async fn ensure_zone_type_consistent(
     }
 }
 
+/// Splits the body of a batch request into individual queries, and validates each
+/// of them with the same rules as the GET autocomplete endpoint.
+pub async fn forward_geocoder_batch_body(
+    content_type: Option<String>,
+    body: Bytes,
+    max_items: usize,
+) -> Result<Vec<Result<ForwardGeocoderQuery, ApiError>>, Rejection> {
+    let content_type = content_type.unwrap_or_default();
+    let items: Vec<Result<ForwardGeocoderQuery, ApiError>> =
+        if content_type.starts_with("application/x-ndjson") {
+            let body = std::str::from_utf8(&body).map_err(|err| {
+                warp::reject::custom(InvalidRequest {
+                    reason: InvalidRequestReason::CannotDeserialize,
+                    info: err.to_string(),
+                })
+            })?;
+            body.lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| serde_json::from_str(line).map_err(validation_error))
+                .collect()
+        } else if content_type.starts_with("text/csv") {
+            csv_batch_queries(&body)?
+        } else {
+            serde_json::from_slice::<Vec<serde_json::Value>>(&body)
+                .map_err(|err| {
+                    warp::reject::custom(InvalidRequest {
+                        reason: InvalidRequestReason::CannotDeserialize,
+                        info: err.to_string(),
+                    })
+                })?
+                .into_iter()
+                .map(|item| serde_json::from_value(item).map_err(validation_error))
+                .collect()
+        };
+
+    if items.len() > max_items {
+        return Err(warp::reject::custom(InvalidRequest {
+            reason: InvalidRequestReason::BatchTooLarge,
+            info: format!(
+                "the batch contains {} queries, the maximum is {}",
+                items.len(),
+                max_items
+            ),
+        }));
+    }
+
+    tracing::info!("Autocomplete batch of {} queries", items.len());
+
+    let mut queries = Vec::with_capacity(items.len());
+    for item in items {
+        let query = match item {
+            Ok(params) => validate_forward_geocoder_params(params).await,
+            Err(err) => Err(err),
+        };
+        queries.push(query);
+    }
+    Ok(queries)
+}
+
+// Reads a CSV batch: the header row gives the names of the query parameters of the
+// autocomplete endpoint, like 'q', 'lat' or 'type[]', and each row is a query. Empty
+// cells are ignored.
+fn csv_batch_queries(
+    body: &[u8],
+) -> Result<Vec<Result<ForwardGeocoderQuery, ApiError>>, Rejection> {
+    let mut reader = csv::Reader::from_reader(body);
+    let headers = reader
+        .headers()
+        .map_err(|err| {
+            warp::reject::custom(InvalidRequest {
+                reason: InvalidRequestReason::CannotDeserialize,
+                info: err.to_string(),
+            })
+        })?
+        .clone();
+    let config = Config::new(2, false);
+    Ok(reader
+        .records()
+        .map(|record| {
+            let record = record.map_err(validation_error)?;
+            let mut params = url::form_urlencoded::Serializer::new(String::new());
+            for (name, value) in headers.iter().zip(record.iter()) {
+                if !value.is_empty() {
+                    params.append_pair(name.trim(), value);
+                }
+            }
+            config
+                .deserialize_str(&params.finish())
+                .map_err(validation_error)
+        })
+        .collect())
+}
+
+/// Validates a forward geocoding query given as a JSON object, with the same rules
+/// as the GET autocomplete endpoint.
+pub async fn validate_forward_geocoder_query(
+    item: serde_json::Value,
+) -> Result<ForwardGeocoderQuery, ApiError> {
+    let params: ForwardGeocoderQuery = serde_json::from_value(item).map_err(validation_error)?;
+    validate_forward_geocoder_params(params).await
+}
+
+/// Validates the parameters of a forward geocoding query with the same rules as the
+/// GET autocomplete endpoint.
+pub async fn validate_forward_geocoder_params(
+    params: ForwardGeocoderQuery,
+) -> Result<ForwardGeocoderQuery, ApiError> {
+    let params = ensure_query_string_not_empty(params)
+        .await
+        .map_err(invalid_request_to_api_error)?;
+    let params = ensure_zone_type_consistent(params)
+        .await
+        .map_err(invalid_request_to_api_error)?;
+    let params = ensure_lat_lon_consistent(params)
+        .await
+        .map_err(invalid_request_to_api_error)?;
+    let params = ensure_min_confidence_in_range(params)
+        .await
+        .map_err(invalid_request_to_api_error)?;
+    ensure_poi_properties_valid(params)
+        .await
+        .map_err(invalid_request_to_api_error)
+}
+
+/// Validates the parameters of a reverse geocoding query with the same rules as the
+/// GET reverse endpoint.
+pub async fn validate_reverse_geocoder_params(
+    params: ReverseGeocoderQuery,
+    max_radius: u32,
+) -> Result<ReverseGeocoderQuery, ApiError> {
+    ensure_radius_in_range(params, max_radius)
+        .await
+        .map_err(invalid_request_to_api_error)
+}
+
+fn validation_error<E: std::fmt::Display>(err: E) -> ApiError {
+    ApiError {
+        short: "validation error".to_string(),
+        long: err.to_string(),
+    }
+}
+
+fn invalid_request_to_api_error(rejection: Rejection) -> ApiError {
+    ApiError {
+        short: "validation error".to_string(),
+        long: rejection
+            .find::<InvalidRequest>()
+            .map(|err| err.info.clone())
+            .unwrap_or_else(|| format!("{:?}", rejection)),
+    }
+}
+
 // This filter extracts the GeoJson shape from the body of the request
 #[instrument]
 pub fn forward_geocoder_body(
@@ -302,68 +783,320 @@ pub fn reverse_geocoder_query(
     })
 }
 
-pub fn features_query() -> impl Filter<Extract = (FeaturesQuery,), Error = Rejection> + Copy {
-    warp::filters::query::raw().and_then(|param: String| async move {
-        let config = Config::new(2, false);
-        tracing::info!("Features query : {}", param);
-        config.deserialize_str(&param).map_err(|err| {
-            warp::reject::custom(InvalidRequest {
-                reason: InvalidRequestReason::CannotDeserialize,
-                info: err.to_string(),
+/// This filter ensures that the radius of a reverse geocoding query, if any, is at most
+/// `max_radius` meters.
+pub async fn ensure_radius_in_range(
+    params: ReverseGeocoderQuery,
+    max_radius: u32,
+) -> Result<ReverseGeocoderQuery, Rejection> {
+    match params.radius {
+        Some(radius) if radius > max_radius => Err(warp::reject::custom(InvalidRequest {
+            reason: InvalidRequestReason::OutOfRangeRadius,
+            info: format!(
+                "the radius is {} meters, the maximum is {}",
+                radius, max_radius
+            ),
+        })),
+        _ => Ok(params),
+    }
+}
+
+pub fn zones_query() -> impl Filter<Extract = (ZonesQuery,), Error = Rejection> + Copy {
+    warp::filters::query::raw()
+        .and_then(|param: String| async move {
+            let config = Config::new(2, false);
+            tracing::info!("Zones query : {}", param);
+            config.deserialize_str(&param).map_err(|err| {
+                warp::reject::custom(InvalidRequest {
+                    reason: InvalidRequestReason::CannotDeserialize,
+                    info: err.to_string(),
+                })
             })
         })
-    })
+        .and_then(ensure_zones_lat_lon_in_range)
 }
 
-pub fn status() -> impl Filter<Extract = (), Error = Rejection> + Clone {
-    warp::get().and(path_prefix()).and(warp::path("status"))
+/// This filter ensures that the location given to the zones endpoint is a valid
+/// WGS84 coordinate.
+pub async fn ensure_zones_lat_lon_in_range(params: ZonesQuery) -> Result<ZonesQuery, Rejection> {
+    if !(-90f64..=90f64).contains(&params.lat) {
+        Err(warp::reject::custom(InvalidRequest {
+            reason: InvalidRequestReason::OutOfRangeLatLonRequest,
+            info: format!(
+                "requested latitude {} is outside of range [-90;90]",
+                params.lat
+            ),
+        }))
+    } else if !(-180f64..=180f64).contains(&params.lon) {
+        Err(warp::reject::custom(InvalidRequest {
+            reason: InvalidRequestReason::OutOfRangeLatLonRequest,
+            info: format!(
+                "requested longitude {} is outside of range [-180;180]",
+                params.lon
+            ),
+        }))
+    } else {
+        Ok(params)
+    }
 }
 
-pub fn metrics() -> impl Filter<Extract = (), Error = Rejection> + Clone {
-    warp::get()
-        .and(path_prefix())
-        .and(warp::path("metrics"))
-        .and(warp::path::end())
+pub fn nearby_query() -> impl Filter<Extract = (NearbyQuery,), Error = Rejection> + Copy {
+    warp::filters::query::raw()
+        .and_then(|param: String| async move {
+            let config = Config::new(2, false);
+            tracing::info!("Nearby query : {}", param);
+            config.deserialize_str(&param).map_err(|err| {
+                warp::reject::custom(InvalidRequest {
+                    reason: InvalidRequestReason::CannotDeserialize,
+                    info: err.to_string(),
+                })
+            })
+        })
+        .and_then(ensure_nearby_consistent)
 }
 
-pub async fn report_invalid(rejection: Rejection) -> Result<impl Reply, Infallible> {
-    let reply = if let Some(err) = rejection.find::<warp::reject::InvalidQuery>() {
-        tracing::info!("Invalid query {:?}", err);
-        warp::reply::with_status(
-            warp::reply::json(&ApiError {
-                short: "invalid query".to_string(),
-                long: err.to_string(),
-            }),
-            StatusCode::BAD_REQUEST,
-        )
-    } else if let Some(err) = rejection.find::<InvalidRequest>() {
-        tracing::info!("Invalid request {:?}", err);
-        warp::reply::with_status(
-            warp::reply::json(&ApiError {
-                short: "validation error".to_string(),
-                long: err.info.clone(),
-            }),
-            StatusCode::BAD_REQUEST,
-        )
-    } else if let Some(err) = rejection.find::<InternalError>() {
-        tracing::info!("Internal error {:?}", err);
-        let short = match err.reason {
-            InternalErrorReason::ObjectNotFoundError => "Unable to find object".to_string(),
-            _ => "query error".to_string(),
-        };
-        warp::reply::with_status(
-            warp::reply::json(&ApiError {
-                short,
-                long: err.info.clone(),
-            }),
-            StatusCode::BAD_REQUEST,
-        )
-    } else if let Some(err) = rejection.find::<MethodNotAllowed>() {
-        tracing::info!("MethodNotAllowed {:?}", err);
-        warp::reply::with_status(
-            warp::reply::json(&ApiError {
-                short: "no route".to_string(),
-                long: err.to_string(),
+/// This filter ensures that the location given to the nearby endpoint is a valid
+/// WGS84 coordinate, and that only pois and stops are requested.
+pub async fn ensure_nearby_consistent(params: NearbyQuery) -> Result<NearbyQuery, Rejection> {
+    if !(-90f64..=90f64).contains(&params.lat) {
+        Err(warp::reject::custom(InvalidRequest {
+            reason: InvalidRequestReason::OutOfRangeLatLonRequest,
+            info: format!(
+                "requested latitude {} is outside of range [-90;90]",
+                params.lat
+            ),
+        }))
+    } else if !(-180f64..=180f64).contains(&params.lon) {
+        Err(warp::reject::custom(InvalidRequest {
+            reason: InvalidRequestReason::OutOfRangeLatLonRequest,
+            info: format!(
+                "requested longitude {} is outside of range [-180;180]",
+                params.lon
+            ),
+        }))
+    } else if params
+        .types
+        .iter()
+        .flatten()
+        .any(|t| !matches!(t, Type::Poi | Type::StopArea))
+    {
+        Err(warp::reject::custom(InvalidRequest {
+            reason: InvalidRequestReason::InconsistentNearbyRequest,
+            info: "only pois and stop areas can be looked for around a location".to_string(),
+        }))
+    } else {
+        Ok(params)
+    }
+}
+
+/// This filter ensures that a nearby request neither searches too far nor returns too
+/// many places.
+pub async fn ensure_nearby_in_range(
+    params: NearbyQuery,
+    max_radius: u32,
+    max_limit: i64,
+) -> Result<NearbyQuery, Rejection> {
+    if params.radius > max_radius {
+        Err(warp::reject::custom(InvalidRequest {
+            reason: InvalidRequestReason::OutOfRangeRadius,
+            info: format!(
+                "the radius is {} meters, the maximum is {}",
+                params.radius, max_radius
+            ),
+        }))
+    } else if !(1..=max_limit).contains(&params.limit) {
+        Err(warp::reject::custom(InvalidRequest {
+            reason: InvalidRequestReason::OutOfRangeLimit,
+            info: format!(
+                "the limit is {}, it must be between 1 and {}",
+                params.limit, max_limit
+            ),
+        }))
+    } else {
+        Ok(params)
+    }
+}
+
+pub fn codes_query() -> impl Filter<Extract = (CodesQuery,), Error = Rejection> + Copy {
+    // All the parameters are optional, so the query string may be missing.
+    warp::query::raw()
+        .or(warp::any().map(String::new))
+        .unify()
+        .and_then(|param: String| async move {
+            let config = Config::new(2, false);
+            tracing::info!("Codes query : {}", param);
+            config.deserialize_str(&param).map_err(|err| {
+                warp::reject::custom(InvalidRequest {
+                    reason: InvalidRequestReason::CannotDeserialize,
+                    info: err.to_string(),
+                })
+            })
+        })
+        .and_then(ensure_codes_types_consistent)
+}
+
+/// Ensures that a key given by the client, like a code type or a poi property, can be
+/// used as the name of a field of the documents: it is made of letters, digits, '_', '-'
+/// and ':' (as in 'ref:INSEE'), so that it cannot address another field, nor several fields.
+pub fn ensure_field_key_valid(key: &str) -> Result<(), Rejection> {
+    let valid = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | ':'));
+    if valid {
+        Ok(())
+    } else {
+        Err(warp::reject::custom(InvalidRequest {
+            reason: InvalidRequestReason::InvalidFieldKey,
+            info: format!(
+                "'{}' is not a valid key, it must only contain letters, digits, '_', '-' and ':'",
+                key
+            ),
+        }))
+    }
+}
+
+/// This filter ensures that the keys of the poi properties are valid field keys.
+pub async fn ensure_poi_properties_valid(
+    params: ForwardGeocoderQuery,
+) -> Result<ForwardGeocoderQuery, Rejection> {
+    for key in params
+        .poi_properties
+        .iter()
+        .flat_map(|properties| properties.keys())
+    {
+        ensure_field_key_valid(key)?;
+    }
+    Ok(params)
+}
+
+/// This filter ensures that only the types of places having codes are requested.
+pub async fn ensure_codes_types_consistent(params: CodesQuery) -> Result<CodesQuery, Rejection> {
+    if params
+        .types
+        .iter()
+        .flatten()
+        .any(|t| !matches!(t, Type::StopArea | Type::Zone | Type::City))
+    {
+        Err(warp::reject::custom(InvalidRequest {
+            reason: InvalidRequestReason::InconsistentCodesRequest,
+            info: "only the types 'public_transport:stop_area', 'zone' and 'city' have codes"
+                .to_string(),
+        }))
+    } else {
+        Ok(params)
+    }
+}
+
+pub fn features_query() -> impl Filter<Extract = (FeaturesQuery,), Error = Rejection> + Copy {
+    warp::filters::query::raw().and_then(|param: String| async move {
+        let config = Config::new(2, false);
+        tracing::info!("Features query : {}", param);
+        let params: FeaturesQuery = config.deserialize_str(&param).map_err(|err| {
+            warp::reject::custom(InvalidRequest {
+                reason: InvalidRequestReason::CannotDeserialize,
+                info: err.to_string(),
+            })
+        })?;
+        validate_tolerance(params.simplify)?;
+        Ok::<_, Rejection>(params)
+    })
+}
+
+// The tolerance of the simplification of boundaries, if any, must be positive.
+fn validate_tolerance(tolerance: Option<f64>) -> Result<(), Rejection> {
+    match tolerance {
+        Some(tolerance) if tolerance < 0.0 || !tolerance.is_finite() => {
+            Err(warp::reject::custom(InvalidRequest {
+                reason: InvalidRequestReason::NegativeTolerance,
+                info: format!(
+                    "the simplification tolerance must be a positive number of degrees, got {}",
+                    tolerance
+                ),
+            }))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// This is the entry warp filter for the GraphQL endpoint.
+///
+/// It accepts GraphQL requests on <prefix> / graphql, either as a GET request with
+/// query parameters, or as a POST request with a JSON body.
+pub fn graphql(
+    schema: BragiSchema,
+) -> impl Filter<Extract = ((BragiSchema, async_graphql::Request),), Error = Rejection> + Clone {
+    path_prefix()
+        .and(warp::path("graphql"))
+        .and(warp::path::end())
+        .and(async_graphql_warp::graphql(schema))
+}
+
+pub fn openapi() -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::get()
+        .and(path_prefix())
+        .and(warp::path("openapi.json"))
+        .and(warp::path::end())
+}
+
+pub fn status() -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::get().and(path_prefix()).and(warp::path("status"))
+}
+
+pub fn metrics() -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::get()
+        .and(path_prefix())
+        .and(warp::path("metrics"))
+        .and(warp::path::end())
+}
+
+pub async fn report_invalid(rejection: Rejection) -> Result<impl Reply, Infallible> {
+    let reply = if let Some(err) = rejection.find::<warp::reject::InvalidQuery>() {
+        tracing::info!("Invalid query {:?}", err);
+        warp::reply::with_status(
+            warp::reply::json(&ApiError {
+                short: "invalid query".to_string(),
+                long: err.to_string(),
+            }),
+            StatusCode::BAD_REQUEST,
+        )
+    } else if let Some(err) = rejection.find::<InvalidRequest>() {
+        tracing::info!("Invalid request {:?}", err);
+        warp::reply::with_status(
+            warp::reply::json(&ApiError {
+                short: "validation error".to_string(),
+                long: err.info.clone(),
+            }),
+            StatusCode::BAD_REQUEST,
+        )
+    } else if let Some(err) = rejection.find::<InternalError>() {
+        tracing::info!("Internal error {:?}", err);
+        let short = match err.reason {
+            InternalErrorReason::ObjectNotFoundError => "Unable to find object".to_string(),
+            _ => "query error".to_string(),
+        };
+        warp::reply::with_status(
+            warp::reply::json(&ApiError {
+                short,
+                long: err.info.clone(),
+            }),
+            StatusCode::BAD_REQUEST,
+        )
+    } else if let Some(err) = rejection.find::<GraphQLBadRequest>() {
+        tracing::info!("Invalid graphql request {:?}", err);
+        warp::reply::with_status(
+            warp::reply::json(&ApiError {
+                short: "invalid graphql request".to_string(),
+                long: err.0.to_string(),
+            }),
+            StatusCode::BAD_REQUEST,
+        )
+    } else if let Some(err) = rejection.find::<MethodNotAllowed>() {
+        tracing::info!("MethodNotAllowed {:?}", err);
+        warp::reply::with_status(
+            warp::reply::json(&ApiError {
+                short: "no route".to_string(),
+                long: err.to_string(),
             }),
             StatusCode::NOT_FOUND,
         )
@@ -402,8 +1135,32 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::adapters::primary::bragi::api::{GeometryFormat, OutputFormat};
+    use crate::adapters::primary::common::filters::{BoundingBox, Filters, PropertyFilter};
+    use crate::adapters::primary::common::settings::{QuerySettings, DEFAULT_PROFILE};
     use places::PlaceDocType;
 
+    #[test]
+    fn should_parse_accept_language_by_quality() {
+        let langs = parse_accept_language("de;q=0.5, fr-CH, en;q=0.8, *;q=0.1, it;q=0");
+        assert_eq!(langs, ["fr-ch", "fr", "en", "de"]);
+        let langs = parse_accept_language("fr-CH, fr;q=0.9, en");
+        assert_eq!(langs, ["fr-ch", "en", "fr"]);
+    }
+
+    #[tokio::test]
+    async fn should_append_fallback_langs_to_accept_language() {
+        let filter = with_langs(vec!["en".to_string(), "fr".to_string()]);
+        let langs = warp::test::request()
+            .header("accept-language", "es, en;q=0.5")
+            .filter(&filter)
+            .await
+            .unwrap();
+        assert_eq!(langs, ["es", "en", "fr"]);
+        let langs = warp::test::request().filter(&filter).await.unwrap();
+        assert_eq!(langs, ["en", "fr"]);
+    }
+
     #[tokio::test]
     async fn should_report_invalid_query_with_no_query() {
         let filter = forward_geocoder_get();
@@ -440,10 +1197,12 @@ mod tests {
             .path("/api/v1/autocomplete?place=paris") // place is an unknown key
             .filter(&filter)
             .await;
+        // Since 'q' may be replaced by address components, it is no longer required to
+        // deserialize the query, and the unknown key leaves a query without any text.
         assert_eq!(
             resp.unwrap_err().find::<InvalidRequest>().unwrap().reason,
-            InvalidRequestReason::CannotDeserialize,
-            "Unknown parameter, cannot deserialize"
+            InvalidRequestReason::EmptyQueryString,
+            "Unknown parameter, no query string nor address component"
         );
     }
 
@@ -471,6 +1230,424 @@ mod tests {
         assert_eq!(resp.unwrap().0.q, String::from("paris"));
     }
 
+    #[tokio::test]
+    async fn should_correctly_extract_structured_query() {
+        let filter = forward_geocoder_get();
+        let resp = warp::test::request()
+            .path("/api/v1/autocomplete?housenumber=12&street=rue%20de%20la%20paix&postcode=75002&city=Paris")
+            .filter(&filter)
+            .await;
+        let params = resp.unwrap().0;
+        assert!(params.q.is_empty());
+        assert_eq!(params.house_number, Some(String::from("12")));
+        assert_eq!(params.full_text(), "12 rue de la paix 75002 Paris");
+    }
+
+    #[tokio::test]
+    async fn should_correctly_extract_bbox_and_country_codes() {
+        let filter = forward_geocoder_get();
+        let resp = warp::test::request()
+            .path("/api/v1/autocomplete?q=paris&bbox=2.2,48.8,2.4,48.9&country_codes=fr,be")
+            .filter(&filter)
+            .await;
+        let params = resp.unwrap().0;
+        assert_eq!(
+            params.bbox,
+            Some(BoundingBox {
+                min_lon: 2.2,
+                min_lat: 48.8,
+                max_lon: 2.4,
+                max_lat: 48.9,
+            })
+        );
+        assert_eq!(
+            params.country_codes,
+            Some(vec![String::from("fr"), String::from("be")])
+        );
+    }
+
+    #[tokio::test]
+    async fn should_correctly_extract_poi_properties() {
+        let filter = forward_geocoder_get();
+        let resp = warp::test::request()
+            .path("/api/v1/autocomplete?q=bar&poi_properties[wheelchair]=yes,limited&poi_properties[opening_hours]=*")
+            .filter(&filter)
+            .await;
+        let filters = Filters::from(resp.unwrap());
+        let mut poi_properties = filters.poi_properties.unwrap();
+        poi_properties.sort_by_key(|property| format!("{:?}", property));
+        assert_eq!(
+            poi_properties,
+            vec![
+                PropertyFilter::Exists(String::from("opening_hours")),
+                PropertyFilter::OneOf(
+                    String::from("wheelchair"),
+                    vec![String::from("yes"), String::from("limited")]
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn should_report_invalid_poi_property_key() {
+        let filter = forward_geocoder_get();
+        let resp = warp::test::request()
+            .path("/api/v1/autocomplete?q=bar&poi_properties[addr:street]=*")
+            .filter(&filter)
+            .await;
+        assert!(resp.is_ok(), "OSM keys are valid keys");
+        let resp = warp::test::request()
+            .path("/api/v1/autocomplete?q=bar&poi_properties[name.fr]=*")
+            .filter(&filter)
+            .await;
+        assert_eq!(
+            resp.unwrap_err().find::<InvalidRequest>().unwrap().reason,
+            InvalidRequestReason::InvalidFieldKey,
+            "Property key addressing another field not allowed"
+        );
+        let resp = validate_forward_geocoder_query(
+            serde_json::json!({ "q": "bar", "poi_properties": { "*": "yes" } }),
+        )
+        .await;
+        assert!(
+            resp.is_err(),
+            "Property key addressing several fields not allowed"
+        );
+    }
+
+    #[tokio::test]
+    async fn should_correctly_extract_max_per_type() {
+        let filter = forward_geocoder_get();
+        let resp = warp::test::request()
+            .path("/api/v1/autocomplete?q=gare&max_per_type[poi]=2&max_per_type[public_transport:stop_area]=1")
+            .filter(&filter)
+            .await;
+        let params = resp.unwrap().0;
+        assert_eq!(
+            params.max_per_type,
+            Some(
+                vec![(Type::Poi, 2), (Type::StopArea, 1)]
+                    .into_iter()
+                    .collect()
+            )
+        );
+        let resp = warp::test::request()
+            .path("/api/v1/autocomplete?q=gare&max_per_type[bus]=2")
+            .filter(&filter)
+            .await;
+        assert!(resp.is_err());
+    }
+
+    #[tokio::test]
+    async fn should_correctly_extract_min_confidence() {
+        let filter = forward_geocoder_get();
+        let resp = warp::test::request()
+            .path("/api/v1/autocomplete?q=paris&min_confidence=0.5")
+            .filter(&filter)
+            .await;
+        assert!(resp.unwrap().0.min_confidence.is_some());
+        let resp = warp::test::request()
+            .path("/api/v1/autocomplete?q=paris&min_confidence=1.5")
+            .filter(&filter)
+            .await;
+        assert_eq!(
+            resp.unwrap_err().find::<InvalidRequest>().unwrap().reason,
+            InvalidRequestReason::OutOfRangeConfidence,
+            "Confidence above 1 not allowed"
+        );
+    }
+
+    #[tokio::test]
+    async fn should_correctly_extract_debug() {
+        let filter = forward_geocoder_get();
+        let resp = warp::test::request()
+            .path("/api/v1/autocomplete?q=paris&debug=true")
+            .filter(&filter)
+            .await;
+        assert!(resp.unwrap().0.debug);
+        let resp = warp::test::request()
+            .path("/api/v1/autocomplete?q=paris")
+            .filter(&filter)
+            .await;
+        assert!(!resp.unwrap().0.debug);
+    }
+
+    #[tokio::test]
+    async fn should_select_the_requested_profile() {
+        let settings = QuerySettings::default();
+        let profiles = std::collections::BTreeMap::from([(
+            String::from("stops_first"),
+            QuerySettings::default(),
+        )]);
+        let filter = with_profile(SettingsHandle::new(settings, profiles));
+        let profile = warp::test::request()
+            .path("/api/v1/autocomplete?q=gare")
+            .filter(&filter)
+            .await
+            .unwrap();
+        assert_eq!(profile.name, DEFAULT_PROFILE);
+        let profile = warp::test::request()
+            .path("/api/v1/autocomplete?q=gare&profile=stops_first")
+            .filter(&filter)
+            .await
+            .unwrap();
+        assert_eq!(profile.name, "stops_first");
+        let profile = warp::test::request()
+            .path("/api/v1/autocomplete?q=gare")
+            .header(PROFILE_HEADER, "stops_first")
+            .filter(&filter)
+            .await
+            .unwrap();
+        assert_eq!(profile.name, "stops_first");
+        let resp = warp::test::request()
+            .path("/api/v1/autocomplete?q=gare&profile=unknown")
+            .filter(&filter)
+            .await;
+        assert_eq!(
+            resp.unwrap_err().find::<InvalidRequest>().unwrap().reason,
+            InvalidRequestReason::UnknownProfile,
+            "Unknown profile not allowed"
+        );
+    }
+
+    #[tokio::test]
+    async fn should_correctly_extract_features_ids() {
+        let filter = features_batch(2);
+        let resp = warp::test::request()
+            .path("/api/v1/features?ids=b,a")
+            .filter(&filter)
+            .await;
+        assert_eq!(
+            resp.unwrap().ids,
+            Some(vec![String::from("b"), String::from("a")])
+        );
+        let resp = warp::test::request()
+            .path("/api/v1/features?ids=a,b,c")
+            .filter(&filter)
+            .await;
+        assert_eq!(
+            resp.unwrap_err().find::<InvalidRequest>().unwrap().reason,
+            InvalidRequestReason::BatchTooLarge,
+            "More ids than the maximum not allowed"
+        );
+        let resp = warp::test::request()
+            .path("/api/v1/features?ids=")
+            .filter(&filter)
+            .await;
+        assert_eq!(
+            resp.unwrap_err().find::<InvalidRequest>().unwrap().reason,
+            InvalidRequestReason::EmptyQueryString,
+            "At least one id required"
+        );
+    }
+
+    #[tokio::test]
+    async fn should_correctly_extract_geometry_format() {
+        let filter = features();
+        let resp = warp::test::request()
+            .path("/api/v1/features/admin:osm:relation:7444?geometry=full&simplify=0.01")
+            .filter(&filter)
+            .await;
+        let (_, params) = resp.unwrap();
+        assert_eq!(params.geometry, GeometryFormat::Full);
+        assert!(params.simplify.is_some());
+        let resp = warp::test::request()
+            .path("/api/v1/features/admin:osm:relation:7444?geometry=full&simplify=-1")
+            .filter(&filter)
+            .await;
+        assert_eq!(
+            resp.unwrap_err().find::<InvalidRequest>().unwrap().reason,
+            InvalidRequestReason::NegativeTolerance,
+            "Negative tolerance not allowed"
+        );
+    }
+
+    #[tokio::test]
+    async fn should_correctly_extract_nearby_query() {
+        let filter = nearby(50_000, 100);
+        let resp = warp::test::request()
+            .path("/api/v1/nearby?lat=48.85&lon=2.35&type[]=poi&poi_types[]=poi_type:amenity:bicycle_rental")
+            .filter(&filter)
+            .await;
+        let params = resp.unwrap();
+        assert_eq!(params.radius, 500);
+        assert_eq!(params.types, Some(vec![Type::Poi]));
+        let resp = warp::test::request()
+            .path("/api/v1/nearby?lat=48.85&lon=2.35&type[]=street")
+            .filter(&filter)
+            .await;
+        assert_eq!(
+            resp.unwrap_err().find::<InvalidRequest>().unwrap().reason,
+            InvalidRequestReason::InconsistentNearbyRequest,
+            "Streets can't be looked for around a location"
+        );
+        let resp = warp::test::request()
+            .path("/api/v1/nearby?lat=98.85&lon=2.35")
+            .filter(&filter)
+            .await;
+        assert_eq!(
+            resp.unwrap_err().find::<InvalidRequest>().unwrap().reason,
+            InvalidRequestReason::OutOfRangeLatLonRequest,
+            "Latitude out of range"
+        );
+    }
+
+    #[tokio::test]
+    async fn should_report_nearby_query_out_of_range() {
+        let filter = nearby(50_000, 100);
+        let resp = warp::test::request()
+            .path("/api/v1/nearby?lat=48.85&lon=2.35&radius=50001")
+            .filter(&filter)
+            .await;
+        assert_eq!(
+            resp.unwrap_err().find::<InvalidRequest>().unwrap().reason,
+            InvalidRequestReason::OutOfRangeRadius,
+            "Radius above the maximum not allowed"
+        );
+        for limit in [0, -1, 101] {
+            let resp = warp::test::request()
+                .path(&format!(
+                    "/api/v1/nearby?lat=48.85&lon=2.35&limit={}",
+                    limit
+                ))
+                .filter(&filter)
+                .await;
+            assert_eq!(
+                resp.unwrap_err().find::<InvalidRequest>().unwrap().reason,
+                InvalidRequestReason::OutOfRangeLimit,
+                "Limit {} not allowed",
+                limit
+            );
+        }
+        let resp = warp::test::request()
+            .path("/api/v1/nearby?lat=48.85&lon=2.35&radius=50000&limit=100")
+            .filter(&filter)
+            .await;
+        assert!(resp.is_ok());
+    }
+
+    #[tokio::test]
+    async fn should_correctly_extract_code() {
+        let filter = codes();
+        let resp = warp::test::request()
+            .path("/api/v1/codes/UIC/87391003?type[]=public_transport:stop_area")
+            .filter(&filter)
+            .await;
+        let (code_type, value, params) = resp.unwrap();
+        assert_eq!(code_type, "UIC");
+        assert_eq!(value, "87391003");
+        assert_eq!(params.types, Some(vec![Type::StopArea]));
+        let resp = warp::test::request()
+            .path("/api/v1/codes/UIC/87391003?type[]=poi")
+            .filter(&filter)
+            .await;
+        assert_eq!(
+            resp.unwrap_err().find::<InvalidRequest>().unwrap().reason,
+            InvalidRequestReason::InconsistentCodesRequest,
+            "Pois have no codes"
+        );
+    }
+
+    #[tokio::test]
+    async fn should_report_invalid_code_type() {
+        let filter = codes();
+        let resp = warp::test::request()
+            .path("/api/v1/codes/ref:INSEE/75056")
+            .filter(&filter)
+            .await;
+        assert_eq!(resp.unwrap().0, "ref:INSEE");
+        for path in ["/api/v1/codes/UIC.*/87391003", "/api/v1/codes/*/87391003"] {
+            let resp = warp::test::request().path(path).filter(&filter).await;
+            assert_eq!(
+                resp.unwrap_err().find::<InvalidRequest>().unwrap().reason,
+                InvalidRequestReason::InvalidFieldKey,
+                "Code type addressing other fields not allowed"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn should_correctly_extract_analyze_query() {
+        let filter = analyze(true);
+        let resp = warp::test::request()
+            .path("/api/v1/analyze?q=rue%20st%20denis&doc_type=street&field[]=label.prefix")
+            .filter(&filter)
+            .await;
+        let params = resp.unwrap();
+        assert_eq!(params.q, "rue st denis");
+        assert_eq!(params.doc_type, "street");
+        assert_eq!(params.field, Some(vec![String::from("label.prefix")]));
+        let resp = warp::test::request()
+            .path("/api/v1/analyze?q=paris")
+            .filter(&filter)
+            .await;
+        let params = resp.unwrap();
+        assert_eq!(params.doc_type, "admin");
+        assert_eq!(params.field, None);
+        let resp = warp::test::request()
+            .path("/api/v1/analyze?doc_type=street")
+            .filter(&filter)
+            .await;
+        assert_eq!(
+            resp.unwrap_err().find::<InvalidRequest>().unwrap().reason,
+            InvalidRequestReason::EmptyQueryString,
+            "The text to analyze is mandatory"
+        );
+    }
+
+    #[tokio::test]
+    async fn should_not_find_disabled_analyze_route() {
+        let filter = analyze(false);
+        let resp = warp::test::request()
+            .path("/api/v1/analyze?q=paris")
+            .filter(&filter)
+            .await;
+        assert!(resp.unwrap_err().is_not_found());
+    }
+
+    #[tokio::test]
+    async fn should_correctly_extract_output_format() {
+        let filter = forward_geocoder_get();
+        let resp = warp::test::request()
+            .path("/api/v1/autocomplete?q=paris")
+            .filter(&filter)
+            .await;
+        assert_eq!(resp.unwrap().0.format, OutputFormat::GeocodeJson);
+        let resp = warp::test::request()
+            .path("/api/v1/autocomplete?q=paris&format=nominatim_v2")
+            .filter(&filter)
+            .await;
+        assert_eq!(resp.unwrap().0.format, OutputFormat::NominatimV2);
+    }
+
+    #[tokio::test]
+    async fn should_correctly_extract_admin_filters() {
+        let filter = forward_geocoder_get();
+        let resp = warp::test::request()
+            .path("/api/v1/autocomplete?q=bellecour&within_admin=admin:osm:relation:120965&focus_admin=admin:osm:relation:120965")
+            .filter(&filter)
+            .await;
+        let params = resp.unwrap().0;
+        assert_eq!(
+            params.within_admin,
+            Some(String::from("admin:osm:relation:120965"))
+        );
+        assert_eq!(params.within_admin, params.focus_admin);
+    }
+
+    #[tokio::test]
+    async fn should_report_invalid_bbox() {
+        let filter = forward_geocoder_get();
+        let resp = warp::test::request()
+            .path("/api/v1/autocomplete?q=paris&bbox=2.2,48.9,2.4")
+            .filter(&filter)
+            .await;
+        assert_eq!(
+            resp.unwrap_err().find::<InvalidRequest>().unwrap().reason,
+            InvalidRequestReason::CannotDeserialize,
+        );
+    }
+
     #[tokio::test]
     async fn should_correctly_extract_types() {
         let filter = forward_geocoder_get();
@@ -606,9 +1783,81 @@ mod tests {
         assert_eq!(resp.0.q, "Bob");
     }
 
+    #[tokio::test]
+    async fn should_correctly_extract_batch_queries() {
+        let filter = forward_geocoder_batch(10);
+        let resp = warp::test::request()
+            .method("POST")
+            .path("/api/v1/autocomplete-batch")
+            .body(r#"[{"q": "paris"}, {"q": ""}, {"q": "lyon", "type": ["street"], "limit": 2}]"#)
+            .filter(&filter)
+            .await
+            .unwrap();
+        assert_eq!(resp.len(), 3);
+        assert_eq!(resp[0].as_ref().unwrap().q, "paris");
+        assert!(resp[1].is_err(), "Empty query string not allowed");
+        let lyon = resp[2].as_ref().unwrap();
+        assert_eq!(lyon.types.as_ref().unwrap(), &[Type::Street]);
+        assert_eq!(lyon.limit, 2);
+    }
+
+    #[tokio::test]
+    async fn should_correctly_extract_ndjson_batch_queries() {
+        let filter = forward_geocoder_batch(10);
+        let resp = warp::test::request()
+            .method("POST")
+            .path("/api/v1/autocomplete-batch")
+            .header("content-type", "application/x-ndjson")
+            .body("{\"q\": \"paris\"}\n\n{\"q\": \"lyon\", \"lat\": 45.7}\nnot json\n")
+            .filter(&filter)
+            .await
+            .unwrap();
+        assert_eq!(resp.len(), 3);
+        assert_eq!(resp[0].as_ref().unwrap().q, "paris");
+        assert!(resp[1].is_err(), "lat without lon not allowed");
+        assert!(resp[2].is_err(), "invalid JSON line");
+    }
+
+    #[tokio::test]
+    async fn should_correctly_extract_csv_batch_queries() {
+        let filter = forward_geocoder_batch(10);
+        let resp = warp::test::request()
+            .method("POST")
+            .path("/api/v1/autocomplete-batch")
+            .header("content-type", "text/csv; charset=utf-8")
+            .body(
+                "q,limit,type[],lat,lon\n\"20 rue hector malot, paris\",1,house,,\nlyon,,,45.7,\n",
+            )
+            .filter(&filter)
+            .await
+            .unwrap();
+        assert_eq!(resp.len(), 2);
+        let paris = resp[0].as_ref().unwrap();
+        assert_eq!(paris.q, "20 rue hector malot, paris");
+        assert_eq!(paris.limit, 1);
+        assert_eq!(paris.types.as_ref().unwrap(), &[Type::House]);
+        assert!(resp[1].is_err(), "lat without lon not allowed");
+    }
+
+    #[tokio::test]
+    async fn should_report_batch_too_large() {
+        let filter = forward_geocoder_batch(1);
+        let resp = warp::test::request()
+            .method("POST")
+            .path("/api/v1/autocomplete-batch")
+            .body(r#"[{"q": "paris"}, {"q": "lyon"}]"#)
+            .filter(&filter)
+            .await;
+        assert_eq!(
+            resp.unwrap_err().find::<InvalidRequest>().unwrap().reason,
+            InvalidRequestReason::BatchTooLarge,
+            "Batch larger than the maximum not allowed"
+        );
+    }
+
     #[tokio::test]
     async fn should_correctly_extract_default_limit() {
-        let filter = reverse_geocoder();
+        let filter = reverse_geocoder(10_000);
         let resp = warp::test::request()
             .path("/api/v1/reverse?lon=6.15&lat=49.14")
             .filter(&filter)
@@ -617,9 +1866,73 @@ mod tests {
         assert_eq!(resp.limit, 1);
     }
 
+    #[tokio::test]
+    async fn should_correctly_extract_reverse_types_and_radius() {
+        let filter = reverse_geocoder(10_000);
+        let resp = warp::test::request()
+            .path(
+                "/api/v1/reverse?lon=6.15&lat=49.14&radius=500&type[]=poi\
+                &type[]=public_transport:stop_area&poi_types[]=amenity:bicycle_rental\
+                &pt_dataset[]=fr",
+            )
+            .filter(&filter)
+            .await
+            .unwrap();
+        assert_eq!(resp.radius, Some(500));
+        assert_eq!(resp.types.unwrap(), [Type::Poi, Type::StopArea]);
+        assert_eq!(resp.poi_types.unwrap(), ["amenity:bicycle_rental"]);
+        assert_eq!(resp.pt_dataset.unwrap(), ["fr"]);
+    }
+
+    #[tokio::test]
+    async fn should_report_out_of_range_reverse_radius() {
+        let filter = reverse_geocoder(10_000);
+        let resp = warp::test::request()
+            .path("/api/v1/reverse?lon=6.15&lat=49.14&radius=4000000000")
+            .filter(&filter)
+            .await;
+        assert_eq!(
+            resp.unwrap_err().find::<InvalidRequest>().unwrap().reason,
+            InvalidRequestReason::OutOfRangeRadius,
+            "Radius larger than the maximum not allowed"
+        );
+    }
+
+    #[tokio::test]
+    async fn should_correctly_extract_zones_query() {
+        let filter = zones();
+        let resp = warp::test::request()
+            .path("/api/v1/zones?lon=2.3522&lat=48.8566&zone_type[]=city&zone_type[]=country")
+            .filter(&filter)
+            .await
+            .unwrap();
+        let zone_types: Vec<&str> = resp
+            .zone_types
+            .unwrap()
+            .iter()
+            .map(|z| z.as_str())
+            .collect();
+        assert_eq!(zone_types, ["city", "country"]);
+        assert_eq!(resp.lang, None);
+    }
+
+    #[tokio::test]
+    async fn should_report_out_of_range_zones_query() {
+        let filter = zones();
+        let resp = warp::test::request()
+            .path("/api/v1/zones?lon=2.3522&lat=98.8566")
+            .filter(&filter)
+            .await;
+        assert_eq!(
+            resp.unwrap_err().find::<InvalidRequest>().unwrap().reason,
+            InvalidRequestReason::OutOfRangeLatLonRequest,
+            "Latitude out of range not allowed"
+        );
+    }
+
     #[tokio::test]
     async fn should_correctly_extract_with_limit() {
-        let filter = reverse_geocoder();
+        let filter = reverse_geocoder(10_000);
         let resp = warp::test::request()
             .path("/api/v1/reverse?lon=6.15&lat=49.14&limit=20")
             .filter(&filter)
@@ -0,0 +1,203 @@
+//! Confidence in the places found by the forward geocoder.
+//!
+//! Elasticsearch scores can't be compared from one query to the other, so they can't tell
+//! whether the first result is a good match. The confidence only depends on the query and
+//! the place: it is the share of the words of the query found in the label of the place,
+//! lowered when the house number does not match, when the place is far from the focus
+//! point, and when the place was only found by the fuzzy query.
+
+use geo::algorithm::haversine_distance::HaversineDistance;
+
+use super::coord::Coord;
+use super::dsl::QueryType;
+use places::Place;
+
+// Factor applied when the query has a house number: the address with another house
+// number is likely the wrong side of the street, other places only miss the house.
+const OTHER_HOUSE_NUMBER: f64 = 0.5;
+const NO_HOUSE_NUMBER: f64 = 0.7;
+// Factor applied to the places found by the fuzzy query, which may have typos.
+const FUZZY: f64 = 0.8;
+// The factor of the distance to the focus point goes from 1, at the focus point,
+// down to MIN_DISTANCE_FACTOR, half way at FOCUS_DISTANCE meters.
+const MIN_DISTANCE_FACTOR: f64 = 0.75;
+const FOCUS_DISTANCE: f64 = 10_000.0;
+
+/// A place found by the forward geocoder, and the confidence in [0, 1] that it is
+/// the place the query is looking for.
+#[derive(Debug)]
+pub struct ScoredPlace {
+    pub place: Place,
+    pub confidence: f64,
+    /// Ids of the near-identical places collapsed into this one.
+    pub collapsed_ids: Vec<String>,
+}
+
+/// Computes the confidence of the places found for a query.
+#[derive(Debug)]
+pub struct Scorer<'a> {
+    words: Vec<String>,
+    house_number: Option<String>,
+    focus: Option<&'a Coord>,
+}
+
+impl<'a> Scorer<'a> {
+    /// `house_number` is the house number of a structured query. Otherwise, the house
+    /// number is the first word of the query, if it starts with a digit.
+    pub fn new(q: &str, house_number: Option<&str>, focus: Option<&'a Coord>) -> Self {
+        let mut words = words(q);
+        let house_number = match house_number {
+            Some(house_number) => {
+                let house_number = house_number.to_lowercase();
+                words.retain(|word| *word != house_number);
+                Some(house_number)
+            }
+            None if words
+                .first()
+                .map_or(false, |word| word.starts_with(|c: char| c.is_ascii_digit())) =>
+            {
+                Some(words.remove(0))
+            }
+            None => None,
+        };
+        Scorer {
+            words,
+            house_number,
+            focus,
+        }
+    }
+
+    /// Returns the confidence of a place found by a query of the given type, rounded
+    /// to two decimals.
+    pub fn confidence(&self, place: &Place, query_type: QueryType) -> f64 {
+        let confidence = self.coverage(place)
+            * self.house_number_factor(place)
+            * self.distance_factor(place)
+            * match query_type {
+                QueryType::PREFIX => 1.0,
+                QueryType::FUZZY => FUZZY,
+            };
+        (confidence.clamp(0.0, 1.0) * 100.0).round() / 100.0
+    }
+
+    // Share of the words of the query found in the label. As the query may be typed
+    // in an autocomplete box, its last word only has to be the start of a word.
+    fn coverage(&self, place: &Place) -> f64 {
+        if self.words.is_empty() {
+            return 1.0;
+        }
+        let label = words(place.label());
+        let last = self.words.len() - 1;
+        let found = self
+            .words
+            .iter()
+            .enumerate()
+            .filter(|(i, word)| {
+                label.iter().any(|label_word| {
+                    label_word == *word || (*i == last && label_word.starts_with(word.as_str()))
+                })
+            })
+            .count();
+        found as f64 / self.words.len() as f64
+    }
+
+    fn house_number_factor(&self, place: &Place) -> f64 {
+        match (&self.house_number, place) {
+            (None, _) => 1.0,
+            (Some(house_number), Place::Addr(addr)) => {
+                if addr.house_number.to_lowercase() == *house_number {
+                    1.0
+                } else {
+                    OTHER_HOUSE_NUMBER
+                }
+            }
+            (Some(_), _) => NO_HOUSE_NUMBER,
+        }
+    }
+
+    fn distance_factor(&self, place: &Place) -> f64 {
+        match self.focus {
+            None => 1.0,
+            Some(focus) => {
+                let focus = geo::Point::new(f64::from(focus.lon), f64::from(focus.lat));
+                let point = geo::Point::new(place.coord().lon(), place.coord().lat());
+                let distance = focus.haversine_distance(&point);
+                MIN_DISTANCE_FACTOR
+                    + (1.0 - MIN_DISTANCE_FACTOR) / (1.0 + distance / FOCUS_DISTANCE)
+            }
+        }
+    }
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use places::{addr::Addr, coord, street::Street};
+
+    fn street(label: &str) -> Place {
+        Place::Street(Street {
+            label: label.to_string(),
+            coord: coord::Coord::new(2.3314, 48.8686),
+            ..Default::default()
+        })
+    }
+
+    fn addr(house_number: &str) -> Place {
+        Place::Addr(Addr {
+            id: format!("addr:{}", house_number),
+            name: String::new(),
+            house_number: house_number.to_string(),
+            street: Street::default(),
+            label: format!("{} Rue de la Paix (Paris)", house_number),
+            coord: coord::Coord::new(2.3314, 48.8686),
+            approx_coord: None,
+            weight: 0.0,
+            zip_codes: vec![],
+            country_codes: vec![],
+            distance: None,
+            context: None,
+        })
+    }
+
+    #[test]
+    fn should_compute_word_coverage() {
+        let scorer = Scorer::new("rue de la paix par", None, None);
+        let confidence = scorer.confidence(&street("Rue de la Paix (Paris)"), QueryType::PREFIX);
+        assert!((confidence - 1.0).abs() < 1e-9);
+        let confidence = scorer.confidence(&street("Rue de la Paix (Lyon)"), QueryType::PREFIX);
+        assert!((confidence - 0.8).abs() < 1e-9);
+        let fuzzy = scorer.confidence(&street("Rue de la Paix (Paris)"), QueryType::FUZZY);
+        assert!((fuzzy - FUZZY).abs() < 1e-9);
+    }
+
+    #[test]
+    fn should_check_the_house_number() {
+        let scorer = Scorer::new("12 rue de la paix", None, None);
+        let same = scorer.confidence(&addr("12"), QueryType::PREFIX);
+        let other = scorer.confidence(&addr("14"), QueryType::PREFIX);
+        let street = scorer.confidence(&street("Rue de la Paix (Paris)"), QueryType::PREFIX);
+        assert!((same - 1.0).abs() < 1e-9);
+        assert!(other < street && street < same);
+    }
+
+    #[test]
+    fn should_lower_the_confidence_far_from_the_focus_point() {
+        let near = Coord::new(48.8686, 2.3314);
+        let far = Coord::new(43.2965, 5.3698);
+        let place = street("Rue de la Paix (Paris)");
+        let near =
+            Scorer::new("rue de la paix", None, Some(&near)).confidence(&place, QueryType::PREFIX);
+        let far =
+            Scorer::new("rue de la paix", None, Some(&far)).confidence(&place, QueryType::PREFIX);
+        assert!((near - 1.0).abs() < 1e-9);
+        assert!(far >= MIN_DISTANCE_FACTOR && far < near);
+    }
+}
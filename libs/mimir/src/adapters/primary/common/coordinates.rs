@@ -0,0 +1,181 @@
+//! Detection of coordinates typed in a query string.
+//!
+//! Users paste coordinates in the search box, either as decimal degrees ("48.8566, 2.3522")
+//! or as degrees, minutes and seconds ("48°51'24"N 2°21'8"E"). Without any hint, the
+//! latitude comes first. The hemisphere letters N, S, E and W give both the sign and the
+//! axis of a component, so "2.3522E 48.8566N" is understood too.
+
+use regex::Regex;
+
+/// Coordinates, in WGS84 decimal degrees.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Coordinates {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+// A component: an optionally signed number of degrees, optionally followed by a degree
+// sign, minutes and seconds. The hemisphere comes either before or after all the components
+// of the query, hence the two regexes, which have the same groups: an empty group stands
+// for the hemisphere which can't be there.
+const COMPONENT: &str = r#"\s*([-+])?(\d+(?:\.\d+)?)\s*(?:(°)\s*(?:(\d+(?:\.\d+)?)\s*'\s*(?:(\d+(?:\.\d+)?)\s*"\s*)?)?)?"#;
+
+lazy_static::lazy_static! {
+    static ref SUFFIXED: Regex = coordinates_regex("()", "([NSEW])?");
+    static ref PREFIXED: Regex = coordinates_regex("([NSEW])?", "()");
+}
+
+fn coordinates_regex(before: &str, after: &str) -> Regex {
+    let component = format!("{}{}{}", before, COMPONENT, after);
+    Regex::new(&format!(
+        r#"^\s*{}\s*[,;/\s]\s*{}\s*$"#,
+        component, component
+    ))
+    .expect("valid regex")
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Axis {
+    Lat,
+    Lon,
+}
+
+/// Returns the coordinates given by the query string, if it is made of coordinates only.
+pub fn parse(q: &str) -> Option<Coordinates> {
+    let q = normalize(q);
+    let captures = SUFFIXED.captures(&q).or_else(|| PREFIXED.captures(&q))?;
+    let group = |i: usize| {
+        captures
+            .get(i)
+            .map(|m| m.as_str())
+            .filter(|group| !group.is_empty())
+    };
+    let (first, first_axis) = component(&group, 1)?;
+    let (second, second_axis) = component(&group, 8)?;
+
+    let (lat, lon) = match (first_axis, second_axis) {
+        (Some(Axis::Lat), Some(Axis::Lat)) | (Some(Axis::Lon), Some(Axis::Lon)) => return None,
+        (Some(Axis::Lon), _) | (_, Some(Axis::Lat)) => (second, first),
+        _ => (first, second),
+    };
+    if (-90f64..=90f64).contains(&lat) && (-180f64..=180f64).contains(&lon) {
+        Some(Coordinates { lat, lon })
+    } else {
+        None
+    }
+}
+
+// Replaces the various signs used for degrees, minutes and seconds by the ascii ones,
+// and uppercases the hemispheres.
+fn normalize(q: &str) -> String {
+    q.replace('º', "°")
+        .replace(&['′', '’', '`'][..], "'")
+        .replace(&['″', '”', '“'][..], "\"")
+        .replace("''", "\"")
+        .to_uppercase()
+}
+
+// Reads the component whose captures start at `first`, and returns its value in
+// decimal degrees, and its axis if a hemisphere is given.
+fn component<'a>(
+    group: &impl Fn(usize) -> Option<&'a str>,
+    first: usize,
+) -> Option<(f64, Option<Axis>)> {
+    let hemisphere = match (group(first), group(first + 6)) {
+        (Some(_), Some(_)) => return None,
+        (hemisphere, None) | (None, hemisphere) => hemisphere,
+    };
+    let negative = group(first + 1) == Some("-");
+    let degrees = group(first + 2)?;
+    let has_degree_sign = group(first + 3).is_some();
+    // Plain integers, like in "12 13", are more likely to be part of an address.
+    if hemisphere.is_none() && !has_degree_sign && !degrees.contains('.') {
+        return None;
+    }
+    let minutes = group(first + 4);
+    let seconds = group(first + 5);
+    // Only the last part of a sexagesimal value can have decimals.
+    if (minutes.is_some() && degrees.contains('.'))
+        || (seconds.is_some() && minutes.map_or(false, |minutes| minutes.contains('.')))
+    {
+        return None;
+    }
+    let minutes: f64 = minutes.map_or(Some(0.0), |minutes| minutes.parse().ok())?;
+    let seconds: f64 = seconds.map_or(Some(0.0), |seconds| seconds.parse().ok())?;
+    if minutes >= 60.0 || seconds >= 60.0 {
+        return None;
+    }
+    let mut value = degrees.parse::<f64>().ok()? + minutes / 60.0 + seconds / 3600.0;
+
+    let axis = match hemisphere {
+        Some("N") => Some(Axis::Lat),
+        Some("S") => {
+            value = -value;
+            Some(Axis::Lat)
+        }
+        Some("E") => Some(Axis::Lon),
+        Some("W") => {
+            value = -value;
+            Some(Axis::Lon)
+        }
+        _ => None,
+    };
+    match (negative, hemisphere) {
+        // A sign and a hemisphere are contradictory or redundant
+        (true, Some(_)) => None,
+        (true, None) => Some((-value, axis)),
+        (false, _) => Some((value, axis)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_coordinates(q: &str, lat: f64, lon: f64) {
+        let coordinates = parse(q).unwrap_or_else(|| panic!("no coordinates in '{}'", q));
+        assert!(
+            (coordinates.lat - lat).abs() < 1e-4 && (coordinates.lon - lon).abs() < 1e-4,
+            "'{}' gives {:?}",
+            q,
+            coordinates
+        );
+    }
+
+    #[test]
+    fn should_parse_decimal_coordinates() {
+        assert_coordinates("48.8566, 2.3522", 48.8566, 2.3522);
+        assert_coordinates("48.8566 2.3522", 48.8566, 2.3522);
+        assert_coordinates(" -33.8688;151.2093 ", -33.8688, 151.2093);
+        assert_coordinates("48.8566N 2.3522E", 48.8566, 2.3522);
+        assert_coordinates("2.3522 E, 48.8566 N", 48.8566, 2.3522);
+        assert_coordinates("S33.8688 E151.2093", -33.8688, 151.2093);
+        assert_coordinates("N 48.8566 E 2.3522", 48.8566, 2.3522);
+    }
+
+    #[test]
+    fn should_parse_sexagesimal_coordinates() {
+        assert_coordinates("48°51'N 2°21'E", 48.85, 2.35);
+        assert_coordinates("48°51'24\"N 2°21'7.9\"E", 48.8567, 2.3522);
+        assert_coordinates("48° 51′ 24″ N, 2° 21′ 8″ E", 48.8567, 2.3522);
+        assert_coordinates("2°21'E 48°51'N", 48.85, 2.35);
+        assert_coordinates("33°52'S 151°12'E", -33.8667, 151.2);
+    }
+
+    #[test]
+    fn should_not_parse_other_queries() {
+        for q in [
+            "12 rue de la paix",
+            "12 13",
+            "75015",
+            "48.8566",
+            "48.8566N 2.3522N",
+            "-48.8566S 2.3522E",
+            "98.8566, 2.3522",
+            "48°61'N 2°21'E",
+            "paris 48.8566, 2.3522",
+        ] {
+            assert_eq!(parse(q), None, "'{}' should not be coordinates", q);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use geo::algorithm::haversine_distance::HaversineDistance;
+
+use super::confidence::ScoredPlace;
+use super::settings::Dedup;
+use places::Place;
+
+/// Collapses the places having the same type and the same normalized label, and
+/// which are closer than the maximum distance of the settings.
+///
+/// Places are expected to be ordered by relevance: the first of the near-identical
+/// places is kept, and the ids of the following ones are added to its `collapsed_ids`.
+pub fn collapse_places(places: Vec<ScoredPlace>, settings: &Dedup) -> Vec<ScoredPlace> {
+    if !settings.enabled {
+        return places;
+    }
+    let mut collapsed: Vec<(String, ScoredPlace)> = Vec::with_capacity(places.len());
+    for scored in places {
+        let label = normalize_label(scored.place.label());
+        let duplicate = collapsed.iter_mut().find(|(kept_label, kept)| {
+            *kept_label == label
+                && std::mem::discriminant(&kept.place) == std::mem::discriminant(&scored.place)
+                && distance(&kept.place, &scored.place) <= f64::from(settings.max_distance)
+        });
+        match duplicate {
+            Some((_, kept)) => {
+                kept.collapsed_ids.push(scored.place.id().to_string());
+                kept.collapsed_ids.extend(scored.collapsed_ids);
+            }
+            None => collapsed.push((label, scored)),
+        }
+    }
+    collapsed.into_iter().map(|(_, scored)| scored).collect()
+}
+
+// Labels only differing by their case, their punctuation or their spacing are the same,
+// eg "Rue de la Paix (Paris)" and "rue de la paix, Paris".
+fn normalize_label(label: &str) -> String {
+    label
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Distance between two places, in meters.
+fn distance(a: &Place, b: &Place) -> f64 {
+    let a = geo::Point::new(a.coord().lon(), a.coord().lat());
+    let b = geo::Point::new(b.coord().lon(), b.coord().lat());
+    a.haversine_distance(&b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use places::{coord::Coord, poi::Poi, street::Street};
+
+    fn scored(place: Place) -> ScoredPlace {
+        ScoredPlace {
+            place,
+            confidence: 1.0,
+            collapsed_ids: Vec::new(),
+        }
+    }
+
+    fn street(id: &str, label: &str, lat: f64, lon: f64) -> ScoredPlace {
+        scored(Place::Street(Street {
+            id: id.to_string(),
+            label: label.to_string(),
+            coord: Coord::new(lon, lat),
+            ..Default::default()
+        }))
+    }
+
+    fn ids(places: &[ScoredPlace]) -> Vec<&str> {
+        places.iter().map(|place| place.place.id()).collect()
+    }
+
+    #[test]
+    fn should_collapse_near_identical_places() {
+        let settings = Dedup {
+            enabled: true,
+            max_distance: 500,
+        };
+        let places = vec![
+            street("a", "Rue de la Paix (Paris)", 48.8686, 2.3314),
+            street("b", "rue de la paix, Paris", 48.8690, 2.3320),
+            // Same label, but far away
+            street("c", "Rue de la Paix (Paris)", 48.8400, 2.3000),
+            // Same label and place, but another type
+            scored(Place::Poi(Poi {
+                id: "d".to_string(),
+                label: "Rue de la Paix (Paris)".to_string(),
+                coord: Coord::new(2.3314, 48.8686),
+                ..Default::default()
+            })),
+        ];
+        let collapsed = collapse_places(places, &settings);
+        assert_eq!(ids(&collapsed), ["a", "c", "d"]);
+        assert_eq!(collapsed[0].collapsed_ids, ["b"]);
+        assert!(collapsed[1].collapsed_ids.is_empty());
+    }
+
+    #[test]
+    fn should_keep_all_places_when_disabled() {
+        let settings = Dedup {
+            enabled: false,
+            max_distance: 500,
+        };
+        let places = vec![
+            street("a", "Rue de la Paix (Paris)", 48.8686, 2.3314),
+            street("b", "Rue de la Paix (Paris)", 48.8686, 2.3314),
+        ];
+        let collapsed = collapse_places(places, &settings);
+        assert_eq!(ids(&collapsed), ["a", "b"]);
+    }
+}
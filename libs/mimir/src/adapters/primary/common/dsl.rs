@@ -21,13 +21,35 @@ pub fn build_query(
     query_type: QueryType,
     excludes: &Option<Vec<String>>,
 ) -> serde_json::Value {
+    // Without a query string, a structured query is searched using the concatenation
+    // of its components, and only the street must match the labels in a prefix query,
+    // since the other components have their own clauses.
+    let (q, matching_text) = match &filters.address {
+        Some(address) if q.is_empty() => (address.to_string(), address.street_text()),
+        _ => (q.to_string(), q.to_string()),
+    };
+    let q = q.as_str();
     let type_query = build_place_type_boost(&settings.type_query);
     let string_query =
         build_string_query(q, lang, &settings.string_query, query_type, &filters.coord);
-    let boosts = build_boosts(q, settings, &filters, query_type);
-    let mut filters = build_filters(filters.shape, filters.poi_types, filters.zone_types);
-    filters.push(build_matching_condition(q, query_type));
+    let mut boosts = build_boosts(q, settings, &filters, query_type);
+    let address_clauses = filters.address.as_ref().map(build_address_clauses);
+    let mut filters = build_filters(filters);
+    match query_type {
+        QueryType::PREFIX if matching_text.is_empty() => {}
+        QueryType::PREFIX => filters.push(build_matching_condition(&matching_text, query_type)),
+        QueryType::FUZZY => filters.push(build_matching_condition(q, query_type)),
+    }
     filters.push(build_house_number_condition(q));
+    // With a structured query, the prefix query requires every address component
+    // to match, while the fuzzy query, used when the prefix query found nothing,
+    // only uses them to boost the places matching the free text.
+    if let Some(address_clauses) = address_clauses {
+        match query_type {
+            QueryType::PREFIX => filters.extend(address_clauses),
+            QueryType::FUZZY => boosts.extend(address_clauses),
+        }
+    }
     match excludes {
         Some(values) => {
             json!({
@@ -146,14 +168,26 @@ fn build_boosts(
         .clone()
         .map(|coord| build_proximity_boost(coord, &decay, weight_boost));
     boosts.push(proximity_boost);
+
+    let focus_admin_boost = filters
+        .focus_admin
+        .as_ref()
+        .map(|admin_id| build_focus_admin_boost(admin_id, settings.importance_query.focus_admin));
+    boosts.push(focus_admin_boost);
     boosts.into_iter().flatten().collect()
 }
 
-fn build_filters(
-    shape: Option<(Geometry, Vec<String>)>,
-    poi_types: Option<Vec<String>>,
-    zone_types: Option<Vec<String>>,
-) -> Vec<serde_json::Value> {
+fn build_filters(filters: filters::Filters) -> Vec<serde_json::Value> {
+    let filters::Filters {
+        shape,
+        poi_types,
+        zone_types,
+        bbox,
+        country_codes,
+        within_admin,
+        poi_properties,
+        ..
+    } = filters;
     let mut filters: Vec<serde_json::Value> = Vec::new();
     if let Some(geoshape_filter) = shape.map(|(geometry, scope)| build_shape_query(geometry, scope))
     {
@@ -162,12 +196,91 @@ fn build_filters(
     if let Some(poi_types_filter) = poi_types.map(build_poi_types_filter) {
         filters.push(poi_types_filter);
     }
+    if let Some(poi_properties_filter) = poi_properties.map(build_poi_properties_filter) {
+        filters.push(poi_properties_filter);
+    }
     if let Some(zone_types_filter) = zone_types.map(build_zone_types_filter) {
         filters.push(zone_types_filter);
     }
+    if let Some(bbox_filter) = bbox.map(build_bbox_filter) {
+        filters.push(bbox_filter);
+    }
+    if let Some(country_codes_filter) = country_codes.map(build_country_codes_filter) {
+        filters.push(country_codes_filter);
+    }
+    if let Some(within_admin_filter) = within_admin.as_deref().map(build_admin_id_clause) {
+        filters.push(within_admin_filter);
+    }
     filters
 }
 
+/// Create a clause for each component of a structured address.
+///
+/// The street is not part of these clauses, it is matched by the free text.
+pub fn build_address_clauses(address: &filters::StructuredAddress) -> Vec<serde_json::Value> {
+    let mut clauses = Vec::new();
+    // The house number and the postcode must be exactly those of the place, so that
+    // '1' does not match '1 bis', nor '75' any postcode. Their keyword subfields are
+    // lowercased, like the analyzed fields.
+    if let Some(house_number) = &address.house_number {
+        clauses.push(json!({
+            "term": {
+                "house_number.keyword": house_number.trim()
+            }
+        }));
+    }
+    if let Some(postcode) = &address.postcode {
+        clauses.push(json!({
+            "term": {
+                "zip_codes.keyword": postcode.trim()
+            }
+        }));
+    }
+    if let Some(city) = &address.city {
+        clauses.push(build_admin_name_clause(city));
+    }
+    if let Some(country) = &address.country {
+        clauses.push(build_admin_name_clause(country));
+    }
+    clauses
+}
+
+/*The place must either be located in an administrative region with the given name, or be
+this administrative region. Addresses don't have their own administrative regions, they
+are found in their street.
+should [
+  match administrative_regions.name
+  match street.administrative_regions.name
+  must {
+    type: admin
+    match name
+  }
+]
+*/
+fn build_admin_name_clause(name: &str) -> serde_json::Value {
+    let name_query = json!({
+        "query": name,
+        "operator": "and"
+    });
+    json!({
+        "bool": {
+            "should": [
+                { "match": { "administrative_regions.name": name_query } },
+                { "match": { "street.administrative_regions.name": name_query } },
+                {
+                    "bool": {
+                        "must": [
+                            { "term": { "type": "admin" } },
+                            { "match": { "name": name_query } }
+                        ]
+                    }
+                }
+            ],
+            "minimum_should_match": 1
+        }
+    })
+}
+
 fn build_weight_depending_on_radius(
     importance_query_settings: &ImportanceQueryBoosts,
     coord: &Option<Coord>,
@@ -357,18 +470,33 @@ fn build_proximity_boost(
 }
 
 pub fn build_reverse_query(distance: &str, lat: f64, lon: f64) -> serde_json::Value {
+    build_reverse_query_with_filters(distance, lat, lon, None)
+}
+
+/// Same as `build_reverse_query`, but restricts the POIs found around the
+/// coordinates to the given poi types.
+pub fn build_reverse_query_with_filters(
+    distance: &str,
+    lat: f64,
+    lon: f64,
+    poi_types: Option<Vec<String>>,
+) -> serde_json::Value {
+    let mut filters = vec![json!({
+        "geo_distance": {
+            "distance": distance,
+            "coord": {
+                "lat": lat,
+                "lon": lon
+            }
+        }
+    })];
+    if let Some(poi_types_filter) = poi_types.map(build_poi_types_filter) {
+        filters.push(poi_types_filter);
+    }
     json!({
     "query": {
         "bool": {
-            "filter": {
-                "geo_distance": {
-                    "distance": distance,
-                    "coord": {
-                        "lat": lat,
-                        "lon": lon
-                    }
-                }
-            }
+            "filter": filters
         }
     },
     "_source": {
@@ -390,6 +518,54 @@ pub fn build_reverse_query(distance: &str, lat: f64, lon: f64) -> serde_json::Va
     })
 }
 
+/// Create a query for the places having the given value for a code, like the UIC
+/// code of a stop. Codes are indexed as a flattened field, keyed by their type.
+pub fn build_codes_query(code_type: &str, value: &str) -> serde_json::Value {
+    json!({
+        "query": {
+            "bool": {
+                "filter": {
+                    "term": {
+                        format!("codes.{}", code_type): value
+                    }
+                }
+            }
+        },
+        "_source": {
+            "excludes": [ "boundary" ]
+        }
+    })
+}
+
+/// Create a query for the administrative regions whose boundary contains the
+/// given coordinates, optionally restricted to some zone types.
+pub fn build_zones_query(lat: f64, lon: f64, zone_types: Option<Vec<String>>) -> serde_json::Value {
+    let mut filters = vec![json!({
+        "geo_shape": {
+            "boundary": {
+                "shape": {
+                    "type": "point",
+                    "coordinates": [lon, lat]
+                },
+                "relation": "intersects"
+            }
+        }
+    })];
+    if let Some(zone_types_filter) = zone_types.map(build_zone_types_filter) {
+        filters.push(zone_types_filter);
+    }
+    json!({
+        "query": {
+            "bool": {
+                "filter": filters
+            }
+        },
+        "_source": {
+            "excludes": [ "boundary" ]
+        }
+    })
+}
+
 /*If there is a shape, all the places listed in shape_scope are restricted to the shape.
 and the places that are not listed are not restricted.
 So if shape_scope = {A, B}, we should end up with something like
@@ -485,6 +661,61 @@ pub fn build_poi_types_filter(poi_types: Vec<String>) -> serde_json::Value {
     })
 }
 
+/*If we search for POIs and we specify conditions on their properties, then we add a filter
+in the same way as for the poi types: POIs must match all the conditions, the other places are
+not filtered. The properties are a flattened field, so each key is a keyword field:
+should [
+  must {               => for pois, check each condition
+    type: poi
+    exists: properties.wheelchair
+    terms: properties.cuisine = {A, B}
+  },
+  must_not {            => or don't filter on properties for other places
+     type: poi
+  }
+]*/
+pub fn build_poi_properties_filter(
+    poi_properties: Vec<filters::PropertyFilter>,
+) -> serde_json::Value {
+    let mut conditions = vec![json!({
+        "term": {
+            "type": "poi"
+        }
+    })];
+    conditions.extend(poi_properties.into_iter().map(|property| match property {
+        filters::PropertyFilter::Exists(key) => json!({
+            "exists": {
+                "field": format!("properties.{}", key)
+            }
+        }),
+        filters::PropertyFilter::OneOf(key, values) => json!({
+            "terms": {
+                format!("properties.{}", key): values
+            }
+        }),
+    }));
+    json!({
+        "bool": {
+            "should": [
+            {
+                "bool": {
+                    "must": conditions
+                }
+            },
+            {
+                "bool": {
+                    "must_not": {
+                        "term": {
+                            "type": "poi"
+                        }
+                    }
+                }
+            }
+            ]
+        }
+    })
+}
+
 /*If we search for administrative regions and we specify zone_types, then we add a filter that should say something
 like:
 If the place is an administrative region, then its zone_type must be part of the given list
@@ -533,15 +764,91 @@ pub fn build_zone_types_filter(zone_types: Vec<String>) -> serde_json::Value {
     })
 }
 
-pub fn build_features_query(indices: &[String], doc_id: &str) -> serde_json::Value {
-    let vec: Vec<serde_json::Value> = indices
+/*The place must either be located in the administrative region with the given id, or be
+this administrative region. Addresses don't have their own administrative regions, they
+are found in their street.
+should [
+  term administrative_regions.id
+  term street.administrative_regions.id
+  term id
+]
+*/
+pub fn build_admin_id_clause(admin_id: &str) -> serde_json::Value {
+    json!({
+        "bool": {
+            "should": [
+                { "term": { "administrative_regions.id": admin_id } },
+                { "term": { "street.administrative_regions.id": admin_id } },
+                { "term": { "id": admin_id } }
+            ],
+            "minimum_should_match": 1
+        }
+    })
+}
+
+/// Create a `Query` that adds `weight` to the score of the places located
+/// in the administrative region with the given id.
+fn build_focus_admin_boost(admin_id: &str, weight: f64) -> serde_json::Value {
+    json!({
+        "constant_score": {
+            "filter": build_admin_id_clause(admin_id),
+            "boost": weight
+        }
+    })
+}
+
+/// Create a filter keeping only the places whose coordinates are inside the box.
+pub fn build_bbox_filter(bbox: filters::BoundingBox) -> serde_json::Value {
+    json!({
+        "geo_bounding_box": {
+            "coord": {
+                "top_left": {
+                    "lat": bbox.max_lat,
+                    "lon": bbox.min_lon
+                },
+                "bottom_right": {
+                    "lat": bbox.min_lat,
+                    "lon": bbox.max_lon
+                }
+            }
+        }
+    })
+}
+
+/// Create a filter keeping only the places located in one of the given countries.
+pub fn build_country_codes_filter(country_codes: Vec<String>) -> serde_json::Value {
+    json!({
+        "terms": {
+            "country_codes": country_codes
+        }
+    })
+}
+
+/// Builds a multi get query, looking for each of the ids in each of the indices.
+///
+/// The boundaries of admins are large, they are only retrieved if `with_boundary` is set.
+pub fn build_features_query(
+    indices: &[String],
+    doc_ids: &[String],
+    with_boundary: bool,
+) -> serde_json::Value {
+    let vec: Vec<serde_json::Value> = doc_ids
         .iter()
-        .map(|index| {
-            json!({
-                "_index": index,
-                "_id" : doc_id,
-                "_source" : {
-                    "exclude" : "boundary"
+        .flat_map(|doc_id| {
+            indices.iter().map(move |index| {
+                if with_boundary {
+                    json!({
+                        "_index": index,
+                        "_id" : doc_id
+                    })
+                } else {
+                    json!({
+                        "_index": index,
+                        "_id" : doc_id,
+                        "_source" : {
+                            "exclude" : "boundary"
+                        }
+                    })
                 }
             })
         })
@@ -625,3 +932,178 @@ fn build_with_weight(build_weight: BuildWeight, types: &Types) -> serde_json::Va
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_match_house_number_and_postcode_exactly() {
+        let address = filters::StructuredAddress {
+            house_number: Some(" 1 bis".to_string()),
+            postcode: Some("75002".to_string()),
+            city: Some("Paris".to_string()),
+            ..Default::default()
+        };
+        let clauses = build_address_clauses(&address);
+        assert_eq!(clauses.len(), 3);
+        assert_eq!(
+            clauses[0],
+            json!({ "term": { "house_number.keyword": "1 bis" } })
+        );
+        assert_eq!(
+            clauses[1],
+            json!({ "term": { "zip_codes.keyword": "75002" } })
+        );
+        assert_eq!(
+            clauses[2]["bool"]["should"][1],
+            json!({ "match": { "street.administrative_regions.name": { "query": "Paris", "operator": "and" } } })
+        );
+    }
+
+    #[test]
+    fn should_sort_reverse_places_by_distance_and_filter_poi_types() {
+        let query = build_reverse_query_with_filters(
+            "300m",
+            48.85,
+            2.35,
+            Some(vec!["poi_type:amenity:bicycle_rental".to_string()]),
+        );
+        let filters = &query["query"]["bool"]["filter"];
+        assert_eq!(
+            filters[0],
+            json!({ "geo_distance": { "distance": "300m", "coord": { "lat": 48.85, "lon": 2.35 } } })
+        );
+        assert_eq!(
+            filters[1]["bool"]["should"][0]["bool"]["must"][1],
+            json!({ "terms": { "poi_type.id": ["poi_type:amenity:bicycle_rental"] } })
+        );
+        assert_eq!(
+            query["sort"][0]["_geo_distance"]["coord"],
+            json!({ "lat": 48.85, "lon": 2.35 })
+        );
+        assert_eq!(query["_source"]["excludes"], json!(["boundary"]));
+
+        let query = build_reverse_query_with_filters("300m", 48.85, 2.35, None);
+        assert_eq!(
+            query["query"]["bool"]["filter"].as_array().unwrap().len(),
+            1
+        );
+    }
+
+    #[test]
+    fn should_find_the_zones_whose_boundary_contains_the_location() {
+        let query = build_zones_query(48.85, 2.35, Some(vec!["city".to_string()]));
+        let filters = &query["query"]["bool"]["filter"];
+        assert_eq!(
+            filters[0],
+            json!({
+                "geo_shape": {
+                    "boundary": {
+                        "shape": { "type": "point", "coordinates": [2.35, 48.85] },
+                        "relation": "intersects"
+                    }
+                }
+            })
+        );
+        assert_eq!(
+            filters[1]["bool"]["should"][0]["bool"]["must"],
+            json!([{ "term": { "type": "admin" } }, { "terms": { "zone_type": ["city"] } }])
+        );
+        assert_eq!(query["_source"]["excludes"], json!(["boundary"]));
+
+        let query = build_zones_query(48.85, 2.35, None);
+        assert_eq!(
+            query["query"]["bool"]["filter"].as_array().unwrap().len(),
+            1
+        );
+    }
+
+    #[test]
+    fn should_filter_on_the_bbox_and_the_country_codes() {
+        let bbox = filters::BoundingBox {
+            min_lon: 2.25,
+            min_lat: 48.81,
+            max_lon: 2.42,
+            max_lat: 48.90,
+        };
+        assert_eq!(
+            build_bbox_filter(bbox),
+            json!({
+                "geo_bounding_box": {
+                    "coord": {
+                        "top_left": { "lat": 48.90, "lon": 2.25 },
+                        "bottom_right": { "lat": 48.81, "lon": 2.42 }
+                    }
+                }
+            })
+        );
+        assert_eq!(
+            build_country_codes_filter(vec!["fr".to_string(), "be".to_string()]),
+            json!({ "terms": { "country_codes": ["fr", "be"] } })
+        );
+    }
+
+    #[test]
+    fn should_look_for_the_admin_in_the_street_of_addresses() {
+        let clause = build_admin_id_clause("admin:osm:relation:7444");
+        assert_eq!(
+            clause,
+            json!({
+                "bool": {
+                    "should": [
+                        { "term": { "administrative_regions.id": "admin:osm:relation:7444" } },
+                        { "term": { "street.administrative_regions.id": "admin:osm:relation:7444" } },
+                        { "term": { "id": "admin:osm:relation:7444" } }
+                    ],
+                    "minimum_should_match": 1
+                }
+            })
+        );
+        assert_eq!(
+            build_focus_admin_boost("admin:osm:relation:7444", 2.5),
+            json!({ "constant_score": { "filter": clause, "boost": 2.5 } })
+        );
+    }
+
+    #[test]
+    fn should_look_for_the_code_by_its_type() {
+        assert_eq!(
+            build_codes_query("UIC", "87391003"),
+            json!({
+                "query": { "bool": { "filter": { "term": { "codes.UIC": "87391003" } } } },
+                "_source": { "excludes": ["boundary"] }
+            })
+        );
+    }
+
+    #[test]
+    fn should_only_filter_the_properties_of_pois() {
+        let filter = build_poi_properties_filter(vec![
+            filters::PropertyFilter::Exists("wheelchair".to_string()),
+            filters::PropertyFilter::OneOf(
+                "cuisine".to_string(),
+                vec!["pizza".to_string(), "kebab".to_string()],
+            ),
+        ]);
+        assert_eq!(
+            filter,
+            json!({
+                "bool": {
+                    "should": [
+                        {
+                            "bool": {
+                                "must": [
+                                    { "term": { "type": "poi" } },
+                                    { "exists": { "field": "properties.wheelchair" } },
+                                    { "terms": { "properties.cuisine": ["pizza", "kebab"] } }
+                                ]
+                            }
+                        },
+                        { "bool": { "must_not": { "term": { "type": "poi" } } } }
+                    ]
+                }
+            })
+        );
+    }
+}
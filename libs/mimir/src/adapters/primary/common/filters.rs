@@ -1,8 +1,12 @@
+use std::fmt;
+use std::str::FromStr;
 use std::time::Duration;
 
 use super::coord::Coord;
 use crate::adapters::primary::bragi::api::Proximity;
 use geojson::Geometry;
+use serde::de::{self, Deserializer};
+use serde::{Deserialize, Serialize, Serializer};
 
 // How to restrict the range of the query... Except for the place type (ie what indices we're
 // searching, since we use the list of types to create the list of indices to search for just
@@ -16,4 +20,167 @@ pub struct Filters {
     pub limit: i64,
     pub timeout: Option<Duration>,
     pub proximity: Option<Proximity>,
+    pub address: Option<StructuredAddress>,
+    pub bbox: Option<BoundingBox>,
+    pub country_codes: Option<Vec<String>>,
+    pub within_admin: Option<String>,
+    pub focus_admin: Option<String>,
+    pub poi_properties: Option<Vec<PropertyFilter>>,
+}
+
+/// A condition on one of the properties of POIs, like `wheelchair=yes`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PropertyFilter {
+    /// The POI has the property, whatever its value.
+    Exists(String),
+    /// The property of the POI has one of the values.
+    OneOf(String, Vec<String>),
+}
+
+impl PropertyFilter {
+    /// Builds the condition on the property `key`, from its value in a query: either
+    /// '*' for any value, or a comma separated list of values.
+    pub fn new(key: String, value: &str) -> Self {
+        let values: Vec<String> = value
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+            .collect();
+        if values.is_empty() || values.iter().any(|value| value == "*") {
+            PropertyFilter::Exists(key)
+        } else {
+            PropertyFilter::OneOf(key, values)
+        }
+    }
+}
+
+/// A rectangle, in WGS84 coordinates.
+///
+/// `min_lon` can be greater than `max_lon` for a box crossing the antimeridian.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingBox {
+    pub min_lon: f64,
+    pub min_lat: f64,
+    pub max_lon: f64,
+    pub max_lat: f64,
+}
+
+// Parses a bounding box given as "minlon,minlat,maxlon,maxlat"
+impl FromStr for BoundingBox {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|value| value.trim().parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|err| format!("invalid bbox '{}': {}", s, err))?;
+        match values[..] {
+            [min_lon, min_lat, max_lon, max_lat] => {
+                let lons_valid = [min_lon, max_lon]
+                    .iter()
+                    .all(|lon| (-180f64..=180f64).contains(lon));
+                let lats_valid = [min_lat, max_lat]
+                    .iter()
+                    .all(|lat| (-90f64..=90f64).contains(lat));
+                if !lons_valid || !lats_valid {
+                    Err(format!("bbox '{}' is outside of WGS84 bounds", s))
+                } else if min_lat > max_lat {
+                    Err(format!(
+                        "bbox '{}' has a min latitude above its max latitude",
+                        s
+                    ))
+                } else {
+                    Ok(BoundingBox {
+                        min_lon,
+                        min_lat,
+                        max_lon,
+                        max_lat,
+                    })
+                }
+            }
+            _ => Err(format!(
+                "invalid bbox '{}': expected minlon,minlat,maxlon,maxlat",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for BoundingBox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{}",
+            self.min_lon, self.min_lat, self.max_lon, self.max_lat
+        )
+    }
+}
+
+impl Serialize for BoundingBox {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for BoundingBox {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+/// The components of an address, when the query is made of separate fields rather
+/// than of a single string.
+#[derive(Clone, Debug, Default)]
+pub struct StructuredAddress {
+    pub street: Option<String>,
+    pub house_number: Option<String>,
+    pub postcode: Option<String>,
+    pub city: Option<String>,
+    pub country: Option<String>,
+}
+
+impl StructuredAddress {
+    pub fn is_empty(&self) -> bool {
+        self.street.is_none()
+            && self.house_number.is_none()
+            && self.postcode.is_none()
+            && self.city.is_none()
+            && self.country.is_none()
+    }
+
+    /// Returns the house number and the street, eg "12 rue de la paix".
+    pub fn street_text(&self) -> String {
+        [&self.house_number, &self.street]
+            .iter()
+            .filter_map(|component| component.as_deref())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+// Formats the components in the order they usually appear in a label,
+// eg "12 rue de la paix 75002 Paris France"
+impl fmt::Display for StructuredAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let components: Vec<&str> = [
+            &self.house_number,
+            &self.street,
+            &self.postcode,
+            &self.city,
+            &self.country,
+        ]
+        .iter()
+        .filter_map(|component| component.as_deref())
+        .collect();
+        write!(f, "{}", components.join(" "))
+    }
 }
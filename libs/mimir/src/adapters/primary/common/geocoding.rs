@@ -1,13 +1,17 @@
 use convert_case::{Case, Casing};
+use geo::algorithm::simplify::Simplify;
+use schemars::JsonSchema;
 use serde::Serialize;
 use std::sync::Arc;
 
+use super::coordinates::Coordinates;
 use crate::adapters::primary::bragi::api;
+use places::i18n_properties::I18nProperties;
 use places::utils::serialize_rect;
 
 /// GeocodeJSON is a an extension of the GeoJSON standard.
 // It must contain the following three items
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, JsonSchema)]
 pub struct GeocodeJsonResponse {
     /// Since GeocodeJSON must be valid GeoJSON, we must identify the type of object.
     /// We are returning a set of features, so the value of format_type will always be
@@ -32,44 +36,134 @@ impl GeocodeJsonResponse {
 }
 
 impl FromWithLang<Vec<places::Place>> for GeocodeJsonResponse {
-    fn from_with_lang(places: Vec<places::Place>, lang: Option<&str>) -> Self {
+    fn from_with_lang(places: Vec<places::Place>, langs: &[String]) -> Self {
         GeocodeJsonResponse::new(
             "".to_string(),
             places
                 .into_iter()
-                .map(|p| Feature::from_with_lang(p, lang))
+                .map(|p| Feature::from_with_lang(p, langs))
                 .collect(),
         )
     }
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, JsonSchema)]
 pub struct Geocoding {
     version: String,
     query: Option<String>,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, JsonSchema)]
 pub struct Feature {
     #[serde(rename = "type")]
     pub feature_type: String,
+    #[schemars(with = "serde_json::Value")]
     pub geometry: geojson::Geometry,
     pub properties: Properties,
     // FIXME distance to the lat lon given in query parameters?
     #[serde(skip_serializing_if = "Option::is_none")]
     pub distance: Option<u32>,
-    // #[serde(skip_serializing_if = "Option::is_none")]
-    // pub context: Option<mimir::Context>,
+    /// Ids of the near-identical places collapsed into this one.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub collapsed_ids: Vec<String>,
+    /// Debugging information, like the explanation of the score of the place.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<serde_json::Value>")]
+    pub context: Option<places::context::Context>,
+}
+
+impl Feature {
+    /// Builds a feature standing for coordinates typed in a query. Like in navitia, its
+    /// id is made of the longitude and the latitude, and its label of the latitude and
+    /// the longitude, in the order they are usually written.
+    pub fn from_coordinates(coordinates: Coordinates) -> Self {
+        let Coordinates { lat, lon } = coordinates;
+        let label = format!("{}, {}", lat, lon);
+        Feature {
+            feature_type: "Feature".to_string(),
+            geometry: geojson::Geometry::new(geojson::Value::Point(vec![lon, lat])),
+            properties: Properties {
+                geocoding: GeocodeJsonProperty {
+                    id: format!("{};{}", lon, lat),
+                    place_type: api::Type::Coord,
+                    zone_type: None,
+                    label: Some(label.clone()),
+                    name: Some(label),
+                    housenumber: None,
+                    street: None,
+                    postcode: None,
+                    city: None,
+                    citycode: None,
+                    level: None,
+                    administrative_regions: vec![],
+                    poi_types: vec![],
+                    properties: vec![],
+                    address: None,
+                    commercial_modes: vec![],
+                    comments: vec![],
+                    physical_modes: vec![],
+                    lines: vec![],
+                    timezone: None,
+                    codes: vec![],
+                    feed_publishers: vec![],
+                    bbox: None,
+                    country_codes: vec![],
+                    confidence: None,
+                },
+            },
+            distance: Some(0),
+            collapsed_ids: vec![],
+            context: None,
+        }
+    }
+
+    /// Returns the longitude and latitude of the feature, if it is a point.
+    pub fn lon_lat(&self) -> Option<(f64, f64)> {
+        match &self.geometry.value {
+            geojson::Value::Point(position) if position.len() >= 2 => {
+                Some((position[0], position[1]))
+            }
+            _ => None,
+        }
+    }
 }
 
-#[derive(Serialize, Debug)]
+/// Builds the geometry of a place in the requested format.
+///
+/// Admins can be returned with their bounding box, or with their boundary, simplified
+/// with the given tolerance in degrees, if any. The other places, and the admins without
+/// a bounding box or a boundary, are returned with their coordinates.
+pub fn place_geometry(
+    place: &places::Place,
+    format: api::GeometryFormat,
+    tolerance: Option<f64>,
+) -> geojson::Geometry {
+    match (place, format) {
+        (places::Place::Admin(admin), api::GeometryFormat::Bbox) => admin
+            .bbox
+            .map(|bbox| geojson::Value::from(&bbox.to_polygon())),
+        (places::Place::Admin(admin), api::GeometryFormat::Full) => {
+            admin.boundary.as_ref().map(|boundary| match tolerance {
+                Some(tolerance) if tolerance > 0.0 => {
+                    geojson::Value::from(&boundary.simplify(&tolerance))
+                }
+                _ => geojson::Value::from(boundary),
+            })
+        }
+        _ => None,
+    }
+    .map(geojson::Geometry::new)
+    .unwrap_or_else(|| geojson::Geometry::from(place))
+}
+
+#[derive(Serialize, Debug, JsonSchema)]
 pub struct Properties {
     pub geocoding: GeocodeJsonProperty,
 }
 
 /// This structure contains the result of a geocoding query
 /// It adheres to the geocodejson spec
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, JsonSchema)]
 pub struct GeocodeJsonProperty {
     pub id: String,
     #[serde(rename = "type")]
@@ -119,12 +213,16 @@ pub struct GeocodeJsonProperty {
         skip_serializing_if = "Option::is_none",
         default
     )]
+    #[schemars(with = "Option<Vec<f64>>")]
     pub bbox: Option<geo_types::Rect<f64>>,
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub country_codes: Vec<String>,
+    /// Confidence in [0, 1] that the place is the one searched by a forward geocoding query.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f64>,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, JsonSchema)]
 pub struct AssociatedAdmin {
     pub id: String,
     pub insee: String,
@@ -138,8 +236,10 @@ pub struct AssociatedAdmin {
         skip_serializing_if = "Option::is_none",
         default
     )]
+    #[schemars(with = "Option<Vec<f64>>")]
     pub bbox: Option<geo_types::Rect<f64>>,
     #[serde(default)]
+    #[schemars(with = "Option<String>")]
     pub zone_type: Option<cosmogony::ZoneType>,
     #[serde(default)]
     pub parent_id: Option<String>, // id of the Admin's parent (from the cosmogony's hierarchy)
@@ -151,15 +251,9 @@ pub struct AssociatedAdmin {
 }
 
 impl FromWithLang<&places::admin::Admin> for AssociatedAdmin {
-    fn from_with_lang(admin: &places::admin::Admin, lang: Option<&str>) -> Self {
-        let (name, label) = if let Some(code) = lang {
-            (
-                admin.names.get(code).unwrap_or(&admin.name),
-                admin.labels.get(code).unwrap_or(&admin.label),
-            )
-        } else {
-            (admin.name.as_ref(), admin.label.as_ref())
-        };
+    fn from_with_lang(admin: &places::admin::Admin, langs: &[String]) -> Self {
+        let name = localized(&admin.names, langs, &admin.name);
+        let label = localized(&admin.labels, langs, &admin.label);
         let codes = admin.codes.iter().fold(Vec::new(), |mut v, (key, value)| {
             v.push(NameValue {
                 name: key.to_string(),
@@ -184,34 +278,46 @@ impl FromWithLang<&places::admin::Admin> for AssociatedAdmin {
     }
 }
 
+/// Returns the value of `properties` in the first language of `langs` for which
+/// there is one, or `default` if there is none.
+pub fn localized<'a>(
+    properties: &'a I18nProperties,
+    langs: &[String],
+    default: &'a str,
+) -> &'a str {
+    langs
+        .iter()
+        .find_map(|lang| properties.get(lang))
+        .unwrap_or(default)
+}
+
+/// Conversion of a place, localizing its names and labels in the first
+/// available language of `langs`, by order of preference.
 pub trait FromWithLang<T> {
-    fn from_with_lang(_: T, lang: Option<&str>) -> Self;
+    fn from_with_lang(_: T, langs: &[String]) -> Self;
 }
 
 impl FromWithLang<places::Place> for Feature {
-    fn from_with_lang(place: places::Place, lang: Option<&str>) -> Feature {
+    fn from_with_lang(place: places::Place, langs: &[String]) -> Feature {
         let geom = geojson::Geometry::from(&place);
         let distance = place.distance();
-        let geocoding = GeocodeJsonProperty::from_with_lang(place, lang);
+        let context = place.context();
+        let geocoding = GeocodeJsonProperty::from_with_lang(place, langs);
         Feature {
             feature_type: "Feature".to_string(),
             geometry: geom,
             properties: Properties { geocoding },
             distance,
+            collapsed_ids: vec![],
+            context,
         }
     }
 }
 
 impl FromWithLang<places::admin::Admin> for GeocodeJsonProperty {
-    fn from_with_lang(admin: places::admin::Admin, lang: Option<&str>) -> GeocodeJsonProperty {
-        let (name, label) = if let Some(lang) = lang {
-            (
-                admin.names.get(lang).unwrap_or(&admin.name),
-                admin.labels.get(lang).unwrap_or(&admin.label),
-            )
-        } else {
-            (admin.name.as_ref(), admin.label.as_ref())
-        };
+    fn from_with_lang(admin: places::admin::Admin, langs: &[String]) -> GeocodeJsonProperty {
+        let name = localized(&admin.names, langs, &admin.name);
+        let label = localized(&admin.labels, langs, &admin.label);
 
         let zone_type = admin
             .zone_type
@@ -237,7 +343,7 @@ impl FromWithLang<places::admin::Admin> for GeocodeJsonProperty {
         let associated_admins = admin
             .administrative_regions
             .iter()
-            .map(|a| AssociatedAdmin::from_with_lang(a, lang))
+            .map(|a| AssociatedAdmin::from_with_lang(a, langs))
             .collect();
 
         GeocodeJsonProperty {
@@ -250,6 +356,7 @@ impl FromWithLang<places::admin::Admin> for GeocodeJsonProperty {
             comments: vec![],
             commercial_modes: vec![],
             country_codes: admin.country_codes,
+            confidence: None,
             feed_publishers: vec![],
             housenumber: None,
             id: admin.id,
@@ -288,7 +395,7 @@ fn get_citycode(admins: &[Arc<places::admin::Admin>]) -> Option<String> {
 }
 
 impl FromWithLang<places::street::Street> for GeocodeJsonProperty {
-    fn from_with_lang(street: places::street::Street, lang: Option<&str>) -> GeocodeJsonProperty {
+    fn from_with_lang(street: places::street::Street, langs: &[String]) -> GeocodeJsonProperty {
         let name = Some(street.name);
         let label = Some(street.label);
         let admins = street.administrative_regions;
@@ -302,7 +409,7 @@ impl FromWithLang<places::street::Street> for GeocodeJsonProperty {
 
         let associated_admins = admins
             .iter()
-            .map(|a| AssociatedAdmin::from_with_lang(a, lang))
+            .map(|a| AssociatedAdmin::from_with_lang(a, langs))
             .collect();
 
         GeocodeJsonProperty {
@@ -315,6 +422,7 @@ impl FromWithLang<places::street::Street> for GeocodeJsonProperty {
             comments: vec![],
             commercial_modes: vec![],
             country_codes: street.country_codes,
+            confidence: None,
             feed_publishers: vec![],
             housenumber: None,
             id: street.id,
@@ -335,7 +443,7 @@ impl FromWithLang<places::street::Street> for GeocodeJsonProperty {
 }
 
 impl FromWithLang<places::addr::Addr> for GeocodeJsonProperty {
-    fn from_with_lang(addr: places::addr::Addr, lang: Option<&str>) -> GeocodeJsonProperty {
+    fn from_with_lang(addr: places::addr::Addr, langs: &[String]) -> GeocodeJsonProperty {
         let label = Some(addr.label);
         let housenumber = Some(addr.house_number.to_string());
         let street_name = Some(addr.street.name.to_string());
@@ -351,7 +459,7 @@ impl FromWithLang<places::addr::Addr> for GeocodeJsonProperty {
 
         let associated_admins = admins
             .iter()
-            .map(|a| AssociatedAdmin::from_with_lang(a, lang))
+            .map(|a| AssociatedAdmin::from_with_lang(a, langs))
             .collect();
 
         GeocodeJsonProperty {
@@ -364,6 +472,7 @@ impl FromWithLang<places::addr::Addr> for GeocodeJsonProperty {
             comments: vec![],
             commercial_modes: vec![],
             country_codes: addr.country_codes,
+            confidence: None,
             feed_publishers: vec![],
             housenumber,
             id: addr.id,
@@ -384,15 +493,9 @@ impl FromWithLang<places::addr::Addr> for GeocodeJsonProperty {
 }
 
 impl FromWithLang<places::poi::Poi> for GeocodeJsonProperty {
-    fn from_with_lang(poi: places::poi::Poi, lang: Option<&str>) -> GeocodeJsonProperty {
-        let (name, label) = if let Some(code) = lang {
-            (
-                poi.names.get(code).unwrap_or(&poi.name),
-                poi.labels.get(code).unwrap_or(&poi.label),
-            )
-        } else {
-            (poi.name.as_ref(), poi.label.as_ref())
-        };
+    fn from_with_lang(poi: places::poi::Poi, langs: &[String]) -> GeocodeJsonProperty {
+        let name = localized(&poi.names, langs, &poi.name);
+        let label = localized(&poi.labels, langs, &poi.label);
         let name = Some(name.to_owned());
         let label = Some(label.to_owned());
         let admins = poi.administrative_regions;
@@ -406,7 +509,7 @@ impl FromWithLang<places::poi::Poi> for GeocodeJsonProperty {
 
         let associated_admins = admins
             .iter()
-            .map(|a| AssociatedAdmin::from_with_lang(a, lang))
+            .map(|a| AssociatedAdmin::from_with_lang(a, langs))
             .collect();
 
         let properties = poi
@@ -423,10 +526,10 @@ impl FromWithLang<places::poi::Poi> for GeocodeJsonProperty {
         GeocodeJsonProperty {
             address: match poi.address {
                 Some(places::Address::Addr(addr)) => {
-                    Some(Box::new(GeocodeJsonProperty::from_with_lang(addr, lang)))
+                    Some(Box::new(GeocodeJsonProperty::from_with_lang(addr, langs)))
                 }
                 Some(places::Address::Street(street)) => {
-                    Some(Box::new(GeocodeJsonProperty::from_with_lang(street, lang)))
+                    Some(Box::new(GeocodeJsonProperty::from_with_lang(street, langs)))
                 }
                 _ => None,
             },
@@ -438,6 +541,7 @@ impl FromWithLang<places::poi::Poi> for GeocodeJsonProperty {
             comments: vec![],
             commercial_modes: vec![],
             country_codes: poi.country_codes,
+            confidence: None,
             feed_publishers: vec![],
             housenumber: None,
             id: poi.id,
@@ -458,7 +562,7 @@ impl FromWithLang<places::poi::Poi> for GeocodeJsonProperty {
 }
 
 impl FromWithLang<places::stop::Stop> for GeocodeJsonProperty {
-    fn from_with_lang(stop: places::stop::Stop, lang: Option<&str>) -> GeocodeJsonProperty {
+    fn from_with_lang(stop: places::stop::Stop, langs: &[String]) -> GeocodeJsonProperty {
         let label = Some(stop.label);
         let name = Some(stop.name);
         let admins = stop.administrative_regions;
@@ -472,7 +576,7 @@ impl FromWithLang<places::stop::Stop> for GeocodeJsonProperty {
 
         let associated_admins = admins
             .iter()
-            .map(|a| AssociatedAdmin::from_with_lang(a, lang))
+            .map(|a| AssociatedAdmin::from_with_lang(a, langs))
             .collect();
 
         let properties = stop
@@ -504,6 +608,7 @@ impl FromWithLang<places::stop::Stop> for GeocodeJsonProperty {
             comments: stop.comments,
             commercial_modes: stop.commercial_modes,
             country_codes: stop.country_codes,
+            confidence: None,
             feed_publishers: stop.feed_publishers,
             housenumber: None,
             id: stop.id,
@@ -524,25 +629,74 @@ impl FromWithLang<places::stop::Stop> for GeocodeJsonProperty {
 }
 
 impl FromWithLang<places::Place> for GeocodeJsonProperty {
-    fn from_with_lang(place: places::Place, lang: Option<&str>) -> Self {
+    fn from_with_lang(place: places::Place, langs: &[String]) -> Self {
         match place {
-            places::Place::Admin(admin) => GeocodeJsonProperty::from_with_lang(admin, lang),
-            places::Place::Street(street) => GeocodeJsonProperty::from_with_lang(street, lang),
-            places::Place::Addr(addr) => GeocodeJsonProperty::from_with_lang(addr, lang),
-            places::Place::Poi(poi) => GeocodeJsonProperty::from_with_lang(poi, lang),
-            places::Place::Stop(poi) => GeocodeJsonProperty::from_with_lang(poi, lang),
+            places::Place::Admin(admin) => GeocodeJsonProperty::from_with_lang(admin, langs),
+            places::Place::Street(street) => GeocodeJsonProperty::from_with_lang(street, langs),
+            places::Place::Addr(addr) => GeocodeJsonProperty::from_with_lang(addr, langs),
+            places::Place::Poi(poi) => GeocodeJsonProperty::from_with_lang(poi, langs),
+            places::Place::Stop(poi) => GeocodeJsonProperty::from_with_lang(poi, langs),
         }
     }
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, JsonSchema)]
 pub struct KeyValue {
     pub key: String,
     pub value: String,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, JsonSchema)]
 pub struct NameValue {
     pub name: String,
     pub value: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo_types::{line_string, Coordinate, MultiPolygon, Polygon, Rect};
+    use places::{admin::Admin, Place};
+
+    fn admin() -> Place {
+        // A square, with a point in the middle of its southern edge.
+        let exterior = line_string![
+            (x: 0.0, y: 0.0),
+            (x: 0.5, y: 0.0),
+            (x: 1.0, y: 0.0),
+            (x: 1.0, y: 1.0),
+            (x: 0.0, y: 1.0),
+            (x: 0.0, y: 0.0),
+        ];
+        Place::Admin(Admin {
+            id: "admin:osm:relation:1".to_string(),
+            boundary: Some(MultiPolygon(vec![Polygon::new(exterior, vec![])])),
+            bbox: Some(Rect::new(
+                Coordinate { x: 0.0, y: 0.0 },
+                Coordinate { x: 1.0, y: 1.0 },
+            )),
+            ..Default::default()
+        })
+    }
+
+    fn exterior_len(geometry: &geojson::Geometry) -> usize {
+        match &geometry.value {
+            geojson::Value::MultiPolygon(polygons) => polygons[0][0].len(),
+            geojson::Value::Polygon(rings) => rings[0].len(),
+            value => panic!("unexpected geometry {:?}", value),
+        }
+    }
+
+    #[test]
+    fn should_build_the_requested_geometry() {
+        let place = admin();
+        let point = place_geometry(&place, api::GeometryFormat::Point, None);
+        assert!(matches!(point.value, geojson::Value::Point(_)));
+        let bbox = place_geometry(&place, api::GeometryFormat::Bbox, None);
+        assert_eq!(exterior_len(&bbox), 5);
+        let full = place_geometry(&place, api::GeometryFormat::Full, None);
+        assert_eq!(exterior_len(&full), 6);
+        let simplified = place_geometry(&place, api::GeometryFormat::Full, Some(0.1));
+        assert_eq!(exterior_len(&simplified), 5);
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use super::confidence::ScoredPlace;
+use super::settings::MergeStrategy;
+use crate::domain::model::hit::Hit;
+use places::Place;
+
+/// Combine the results of the prefix and the fuzzy queries according to `strategy`.
+///
+/// Places are deduplicated by id, and at most `limit` places are returned.
+pub fn merge_places(
+    prefix: Vec<Hit<Place>>,
+    fuzzy: Vec<Hit<Place>>,
+    strategy: MergeStrategy,
+    limit: usize,
+) -> Vec<Place> {
+    let hits = match strategy {
+        MergeStrategy::FirstNonEmpty => {
+            if prefix.is_empty() {
+                fuzzy
+            } else {
+                prefix
+            }
+        }
+        MergeStrategy::Interleave => interleave(prefix, fuzzy),
+        MergeStrategy::ScoreNormalized => {
+            let mut hits = normalize_scores(prefix);
+            hits.extend(normalize_scores(fuzzy));
+            // The sort is stable, so for equal scores, prefix hits come first.
+            hits.sort_by(|a, b| {
+                b.score
+                    .unwrap_or(0.0)
+                    .partial_cmp(&a.score.unwrap_or(0.0))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            hits
+        }
+    };
+
+    let mut ids = HashSet::new();
+    hits.into_iter()
+        .map(|hit| hit.document)
+        .filter(|place| ids.insert(place.id().to_string()))
+        .take(limit)
+        .collect()
+}
+
+/// Keeps at most `limit` places, without more places of a type than its quota.
+///
+/// Quotas are keyed by the types of the `type[]` parameter, and the types without a
+/// quota are not limited. Places keep their order, so a place over its quota leaves
+/// its rank to the next place of another type.
+pub fn apply_quotas(
+    places: Vec<ScoredPlace>,
+    quotas: &BTreeMap<String, usize>,
+    limit: usize,
+) -> Vec<ScoredPlace> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    places
+        .into_iter()
+        .filter(|scored| {
+            let place_type = type_name(&scored.place);
+            let count = counts.entry(place_type).or_insert(0);
+            *count += 1;
+            quotas
+                .get(place_type)
+                .map_or(true, |quota| *count <= *quota)
+        })
+        .take(limit)
+        .collect()
+}
+
+// The name of the type of the place in the `type[]` parameter.
+fn type_name(place: &Place) -> &'static str {
+    match place {
+        Place::Addr(_) => "house",
+        Place::Poi(_) => "poi",
+        Place::Stop(_) => "public_transport:stop_area",
+        Place::Street(_) => "street",
+        Place::Admin(_) => "zone",
+    }
+}
+
+fn interleave(prefix: Vec<Hit<Place>>, fuzzy: Vec<Hit<Place>>) -> Vec<Hit<Place>> {
+    let mut hits = Vec::with_capacity(prefix.len() + fuzzy.len());
+    let mut prefix = prefix.into_iter();
+    let mut fuzzy = fuzzy.into_iter();
+    loop {
+        match (prefix.next(), fuzzy.next()) {
+            (None, None) => break,
+            (p, f) => {
+                hits.extend(p);
+                hits.extend(f);
+            }
+        }
+    }
+    hits
+}
+
+// Scores are not comparable from one query to the other, so we bring
+// each query's scores in [0, 1] by dividing them by the best score.
+fn normalize_scores(hits: Vec<Hit<Place>>) -> Vec<Hit<Place>> {
+    let max_score = hits.iter().filter_map(|hit| hit.score).fold(0.0, f64::max);
+    hits.into_iter()
+        .map(|hit| Hit {
+            score: hit.score.map(|score| {
+                if max_score > 0.0 {
+                    score / max_score
+                } else {
+                    0.0
+                }
+            }),
+            ..hit
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use places::{admin::Admin, poi::Poi};
+
+    fn hit(id: &str, score: f64) -> Hit<Place> {
+        Hit {
+            document: Place::Admin(Admin {
+                id: id.to_string(),
+                ..Default::default()
+            }),
+            score: Some(score),
+            explanation: None,
+        }
+    }
+
+    fn ids(places: &[Place]) -> Vec<&str> {
+        places.iter().map(|place| place.id()).collect()
+    }
+
+    #[test]
+    fn should_return_first_non_empty_results() {
+        let prefix = vec![hit("a", 1.0)];
+        let fuzzy = vec![hit("b", 2.0)];
+        let places = merge_places(prefix, fuzzy.clone(), MergeStrategy::FirstNonEmpty, 10);
+        assert_eq!(ids(&places), ["a"]);
+        let places = merge_places(vec![], fuzzy, MergeStrategy::FirstNonEmpty, 10);
+        assert_eq!(ids(&places), ["b"]);
+    }
+
+    #[test]
+    fn should_interleave_results_without_duplicates() {
+        let prefix = vec![hit("a", 1.0), hit("b", 0.5), hit("c", 0.2)];
+        let fuzzy = vec![hit("d", 3.0), hit("a", 2.0)];
+        let places = merge_places(prefix, fuzzy, MergeStrategy::Interleave, 10);
+        assert_eq!(ids(&places), ["a", "d", "b", "c"]);
+    }
+
+    #[test]
+    fn should_merge_results_by_normalized_score() {
+        let prefix = vec![hit("a", 10.0), hit("b", 2.0)];
+        let fuzzy = vec![hit("c", 100.0), hit("d", 80.0), hit("a", 10.0)];
+        let places = merge_places(prefix, fuzzy, MergeStrategy::ScoreNormalized, 3);
+        assert_eq!(ids(&places), ["a", "c", "d"]);
+    }
+
+    #[test]
+    fn should_apply_quotas_per_type() {
+        let poi = |id: &str| {
+            Place::Poi(Poi {
+                id: id.to_string(),
+                ..Default::default()
+            })
+        };
+        let places = vec![
+            poi("p1"),
+            poi("p2"),
+            poi("p3"),
+            hit("a1", 1.0).document,
+            poi("p4"),
+            hit("a2", 1.0).document,
+        ]
+        .into_iter()
+        .map(|place| ScoredPlace {
+            place,
+            confidence: 1.0,
+            collapsed_ids: Vec::new(),
+        })
+        .collect();
+        let quotas = BTreeMap::from([("poi".to_string(), 2)]);
+        let places: Vec<Place> = apply_quotas(places, &quotas, 3)
+            .into_iter()
+            .map(|scored| scored.place)
+            .collect();
+        assert_eq!(ids(&places), ["p1", "p2", "a1"]);
+    }
+}
@@ -1,5 +1,11 @@
+pub mod confidence;
 pub mod coord;
+pub mod coordinates;
+pub mod dedup;
 pub mod dsl;
 pub mod filters;
 pub mod geocoding;
+pub mod merge;
+pub mod nominatim;
+pub mod pelias;
 pub mod settings;
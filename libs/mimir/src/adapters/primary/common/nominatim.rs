@@ -0,0 +1,225 @@
+//! Nominatim compatible responses.
+//!
+//! Places are serialized like the results of Nominatim's `json` and `jsonv2` formats,
+//! for the clients written against Nominatim. Nominatim's numeric `place_id` has no
+//! equivalent in mimir, so it holds the id of the place.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use super::geocoding::{Feature, GeocodeJsonProperty};
+use crate::adapters::primary::bragi::api::Type;
+
+const LICENCE: &str = "Data © OpenStreetMap contributors, ODbL 1.0. https://osm.org/copyright";
+
+/// A place, in Nominatim's `json` format.
+#[derive(Serialize, Debug)]
+pub struct NominatimPlace {
+    pub place_id: String,
+    pub licence: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub osm_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub osm_id: Option<String>,
+    /// min latitude, max latitude, min longitude, max longitude
+    pub boundingbox: [String; 4],
+    pub lat: String,
+    pub lon: String,
+    pub display_name: String,
+    pub class: String,
+    #[serde(rename = "type")]
+    pub place_type: String,
+    pub address: BTreeMap<String, String>,
+}
+
+/// A place, in Nominatim's `jsonv2` format.
+#[derive(Serialize, Debug)]
+pub struct NominatimPlaceV2 {
+    pub place_id: String,
+    pub licence: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub osm_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub osm_id: Option<String>,
+    pub boundingbox: [String; 4],
+    pub lat: String,
+    pub lon: String,
+    pub display_name: String,
+    pub place_rank: u32,
+    pub category: String,
+    #[serde(rename = "type")]
+    pub place_type: String,
+    pub addresstype: String,
+    pub name: String,
+    pub address: BTreeMap<String, String>,
+}
+
+impl From<Feature> for NominatimPlace {
+    fn from(feature: Feature) -> Self {
+        let v2 = NominatimPlaceV2::from(feature);
+        NominatimPlace {
+            place_id: v2.place_id,
+            licence: v2.licence,
+            osm_type: v2.osm_type,
+            osm_id: v2.osm_id,
+            boundingbox: v2.boundingbox,
+            lat: v2.lat,
+            lon: v2.lon,
+            display_name: v2.display_name,
+            class: v2.category,
+            place_type: v2.place_type,
+            address: v2.address,
+        }
+    }
+}
+
+impl From<Feature> for NominatimPlaceV2 {
+    fn from(feature: Feature) -> Self {
+        let (lon, lat) = feature.lon_lat().unwrap_or_default();
+        let properties = feature.properties.geocoding;
+        let (osm_type, osm_id) = match osm_ref(&properties.id) {
+            Some((osm_type, osm_id)) => (Some(osm_type), Some(osm_id)),
+            None => (None, None),
+        };
+        let (category, place_type) = category_and_type(&properties);
+        let addresstype = match properties.place_type {
+            Type::Zone | Type::City => properties
+                .zone_type
+                .clone()
+                .unwrap_or_else(|| "administrative".to_string()),
+            Type::Street => "road".to_string(),
+            Type::House => "house_number".to_string(),
+            Type::Poi | Type::StopArea | Type::Coord => place_type.clone(),
+        };
+        // [min lat, max lat, min lon, max lon], whereas geojson bboxes are
+        // [min lon, min lat, max lon, max lat]
+        let boundingbox = match properties.bbox {
+            Some(bbox) => [bbox.min().y, bbox.max().y, bbox.min().x, bbox.max().x],
+            None => [lat, lat, lon, lon],
+        }
+        .map(|value| value.to_string());
+
+        NominatimPlaceV2 {
+            place_id: properties.id.clone(),
+            licence: LICENCE.to_string(),
+            osm_type,
+            osm_id,
+            boundingbox,
+            lat: lat.to_string(),
+            lon: lon.to_string(),
+            display_name: properties.label.clone().unwrap_or_default(),
+            place_rank: place_rank(&properties),
+            category,
+            place_type,
+            addresstype,
+            name: properties.name.clone().unwrap_or_default(),
+            address: address(&properties),
+        }
+    }
+}
+
+// Extracts the OSM type and id from the ids built from OSM objects,
+// like 'admin:osm:relation:7444' or 'poi:osm:node:12345'.
+fn osm_ref(id: &str) -> Option<(String, String)> {
+    let mut parts = id.split(':').skip_while(|part| *part != "osm").skip(1);
+    let osm_type = match parts.next()? {
+        "node" => "node",
+        "way" => "way",
+        "relation" => "relation",
+        _ => return None,
+    };
+    let osm_id = parts.next().filter(|id| id.parse::<u64>().is_ok())?;
+    Some((osm_type.to_string(), osm_id.to_string()))
+}
+
+fn category_and_type(properties: &GeocodeJsonProperty) -> (String, String) {
+    match properties.place_type {
+        Type::Zone | Type::City => ("boundary".to_string(), "administrative".to_string()),
+        Type::Street => ("highway".to_string(), "road".to_string()),
+        Type::House => ("place".to_string(), "house".to_string()),
+        Type::StopArea => ("public_transport".to_string(), "stop_area".to_string()),
+        Type::Coord => ("place".to_string(), "coordinates".to_string()),
+        Type::Poi => {
+            // OSM poi types are like 'poi_type:amenity:bicycle_rental'
+            let poi_type = properties
+                .poi_types
+                .first()
+                .map(|poi_type| poi_type.id.trim_start_matches("poi_type:"))
+                .unwrap_or_default();
+            match poi_type.split_once(':') {
+                Some((key, value)) => (key.to_string(), value.to_string()),
+                None if poi_type.is_empty() => ("poi".to_string(), "yes".to_string()),
+                None => ("poi".to_string(), poi_type.to_string()),
+            }
+        }
+    }
+}
+
+// The place rank is Nominatim's notion of the size of a place, from 4 for
+// countries to 30 for houses and pois.
+fn place_rank(properties: &GeocodeJsonProperty) -> u32 {
+    match properties.place_type {
+        Type::Zone | Type::City => match properties.zone_type.as_deref() {
+            Some("country") => 4,
+            Some("country_region") => 6,
+            Some("state") => 8,
+            Some("state_district") => 12,
+            Some("city") => 16,
+            Some("city_district") => 18,
+            Some("suburb") => 20,
+            _ => properties.level.map_or(16, |level| level * 2),
+        },
+        Type::Street => 26,
+        Type::House | Type::Poi | Type::StopArea | Type::Coord => 30,
+    }
+}
+
+// Nominatim's address details: the address of the place, and the names of
+// the administrative regions it belongs to, keyed by their type.
+fn address(properties: &GeocodeJsonProperty) -> BTreeMap<String, String> {
+    let mut address = BTreeMap::new();
+    for admin in &properties.administrative_regions {
+        let key = match admin.zone_type.as_ref().map(|zone_type| zone_type.as_str()) {
+            Some("country") => "country",
+            Some("country_region") => "region",
+            Some("state") => "state",
+            Some("state_district") => "state_district",
+            Some("city") => "city",
+            Some("city_district") => "city_district",
+            Some("suburb") => "suburb",
+            _ => continue,
+        };
+        address.insert(key.to_string(), admin.name.clone());
+    }
+    if let Some(housenumber) = &properties.housenumber {
+        address.insert("house_number".to_string(), housenumber.clone());
+    }
+    if let Some(street) = &properties.street {
+        address.insert("road".to_string(), street.clone());
+    }
+    if let Some(postcode) = &properties.postcode {
+        address.insert("postcode".to_string(), postcode.clone());
+    }
+    if let Some(city) = &properties.city {
+        address.insert("city".to_string(), city.clone());
+    }
+    if let Some(country_code) = properties.country_codes.first() {
+        address.insert("country_code".to_string(), country_code.to_lowercase());
+    }
+    address
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_extract_osm_ref_from_id() {
+        assert_eq!(
+            osm_ref("admin:osm:relation:7444"),
+            Some(("relation".to_string(), "7444".to_string()))
+        );
+        assert_eq!(osm_ref("addr:2.35;48.85:12"), None);
+        assert_eq!(osm_ref("poi:osm:node:abc"), None);
+    }
+}
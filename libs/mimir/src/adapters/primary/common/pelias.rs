@@ -0,0 +1,222 @@
+//! Pelias compatible responses.
+//!
+//! Places are serialized like the GeoJSON responses of Pelias, for the clients
+//! written against Pelias. Places come from mimir, so their `source` is 'mimir',
+//! and their `gid` is built from their layer and their id.
+
+use serde::Serialize;
+
+use super::geocoding::{Feature, GeocodeJsonProperty};
+use crate::adapters::primary::bragi::api::Type;
+
+const SOURCE: &str = "mimir";
+
+/// A Pelias response: a GeoJSON feature collection.
+#[derive(Serialize, Debug)]
+pub struct PeliasResponse {
+    pub geocoding: PeliasGeocoding,
+    #[serde(rename = "type")]
+    pub format_type: String,
+    pub features: Vec<PeliasFeature>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bbox: Option<[f64; 4]>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PeliasGeocoding {
+    pub version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<PeliasQuery>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PeliasQuery {
+    pub text: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PeliasFeature {
+    #[serde(rename = "type")]
+    pub feature_type: String,
+    pub geometry: geojson::Geometry,
+    pub properties: PeliasProperties,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bbox: Option<[f64; 4]>,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct PeliasProperties {
+    pub id: String,
+    pub gid: String,
+    pub layer: String,
+    pub source: String,
+    pub source_id: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub housenumber: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub street: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub postalcode: Option<String>,
+    /// Distance to the focus point, in kilometers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distance: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f64>,
+    pub accuracy: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub macroregion: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub county: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locality: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub borough: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub neighbourhood: Option<String>,
+    pub label: String,
+}
+
+impl PeliasResponse {
+    /// Builds a response with the given features, and the text of the query, if any.
+    pub fn new(text: Option<String>, features: Vec<Feature>) -> Self {
+        let features: Vec<PeliasFeature> = features.into_iter().map(PeliasFeature::from).collect();
+        let bbox = features
+            .iter()
+            .map(|feature| {
+                feature
+                    .bbox
+                    .unwrap_or_else(|| point_bbox(&feature.geometry))
+            })
+            .reduce(|a, b| {
+                [
+                    a[0].min(b[0]),
+                    a[1].min(b[1]),
+                    a[2].max(b[2]),
+                    a[3].max(b[3]),
+                ]
+            });
+        PeliasResponse {
+            geocoding: PeliasGeocoding {
+                version: "0.2".to_string(),
+                query: text.map(|text| PeliasQuery { text }),
+            },
+            format_type: "FeatureCollection".to_string(),
+            features,
+            bbox,
+        }
+    }
+}
+
+fn point_bbox(geometry: &geojson::Geometry) -> [f64; 4] {
+    match &geometry.value {
+        geojson::Value::Point(position) if position.len() >= 2 => {
+            [position[0], position[1], position[0], position[1]]
+        }
+        _ => [0.0; 4],
+    }
+}
+
+impl From<Feature> for PeliasFeature {
+    fn from(feature: Feature) -> Self {
+        let distance = feature.distance.map(|meters| f64::from(meters) / 1000.0);
+        let geocoding = feature.properties.geocoding;
+        let bbox = geocoding
+            .bbox
+            .map(|bbox| [bbox.min().x, bbox.min().y, bbox.max().x, bbox.max().y]);
+        let accuracy = match geocoding.place_type {
+            Type::Zone | Type::City => "centroid",
+            _ => "point",
+        };
+        let layer = layer(&geocoding);
+        let mut properties = PeliasProperties {
+            gid: format!("{}:{}:{}", SOURCE, layer, geocoding.id),
+            id: geocoding.id.clone(),
+            layer: layer.to_string(),
+            source: SOURCE.to_string(),
+            source_id: geocoding.id.clone(),
+            name: geocoding.name.clone().unwrap_or_default(),
+            housenumber: geocoding.housenumber.clone(),
+            street: geocoding.street.clone(),
+            postalcode: geocoding.postcode.clone(),
+            distance,
+            confidence: geocoding.confidence,
+            accuracy: accuracy.to_string(),
+            country_code: geocoding
+                .country_codes
+                .first()
+                .map(|country_code| country_code.to_uppercase()),
+            label: geocoding.label.clone().unwrap_or_default(),
+            ..Default::default()
+        };
+        for admin in &geocoding.administrative_regions {
+            let name = Some(admin.name.clone());
+            match admin.zone_type.as_ref().map(|zone_type| zone_type.as_str()) {
+                Some("country") => properties.country = name,
+                Some("country_region") => properties.macroregion = name,
+                Some("state") => properties.region = name,
+                Some("state_district") => properties.county = name,
+                Some("city") => properties.locality = name,
+                Some("city_district") => properties.borough = name,
+                Some("suburb") => properties.neighbourhood = name,
+                _ => {}
+            }
+        }
+        PeliasFeature {
+            feature_type: "Feature".to_string(),
+            geometry: feature.geometry,
+            properties,
+            bbox,
+        }
+    }
+}
+
+// Pelias' layers are the types of places, with one layer per type of admin.
+fn layer(geocoding: &GeocodeJsonProperty) -> &'static str {
+    match geocoding.place_type {
+        Type::House => "address",
+        Type::Street => "street",
+        Type::Poi => "venue",
+        Type::StopArea => "stop",
+        Type::Coord => "coordinates",
+        Type::Zone | Type::City => match geocoding.zone_type.as_deref() {
+            Some("country") => "country",
+            Some("country_region") => "macroregion",
+            Some("state") => "region",
+            Some("state_district") => "county",
+            Some("city_district") => "borough",
+            Some("suburb") => "neighbourhood",
+            _ => "locality",
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::primary::common::geocoding::FromWithLang;
+    use places::{admin::Admin, Place};
+
+    #[test]
+    fn should_put_admins_in_their_layer() {
+        let admin = Admin {
+            id: "admin:osm:relation:7444".to_string(),
+            name: "Paris".to_string(),
+            label: "Paris (75000-75116), Île-de-France, France".to_string(),
+            zone_type: Some(cosmogony::ZoneType::City),
+            ..Default::default()
+        };
+        let feature = Feature::from_with_lang(Place::Admin(admin), &[]);
+        let response = PeliasResponse::new(Some("paris".to_string()), vec![feature]);
+        let properties = &response.features[0].properties;
+        assert_eq!(properties.layer, "locality");
+        assert_eq!(properties.gid, "mimir:locality:admin:osm:relation:7444");
+        assert_eq!(properties.accuracy, "centroid");
+    }
+}
@@ -1,6 +1,10 @@
 use serde::{Deserialize, Serialize};
 use snafu::{ResultExt, Snafu};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
 use std::path::Path;
+use std::sync::{Arc, RwLock};
 use tokio::fs::File;
 use tokio::io::{AsyncReadExt, BufReader};
 
@@ -11,6 +15,9 @@ pub enum Error {
 
     #[snafu(display("TOML Error: {}", source))]
     InvalidFileContent { source: toml::de::Error },
+
+    #[snafu(display("Invalid Overrides: {}", source))]
+    InvalidOverrides { source: serde_json::Error },
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -81,6 +88,7 @@ pub struct Weights {
 pub struct ImportanceQueryBoosts {
     pub proximity: Proximity,
     pub weights: Weights,
+    pub focus_admin: f64, // boost of the places located in the focus admin
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -88,8 +96,77 @@ pub struct ReverseQuery {
     pub radius: u32, // search radius in meters
 }
 
+/// How the results of the prefix and the fuzzy queries are combined
+/// into the response of the forward geocoder. Both queries are always sent
+/// to Elasticsearch, in a single multi search request.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeStrategy {
+    /// Results of the prefix query, or results of the fuzzy query if the
+    /// prefix query found nothing.
+    FirstNonEmpty,
+    /// Results of both queries, taken alternately, starting with the prefix query.
+    Interleave,
+    /// Results of both queries, ordered by their score normalized by the
+    /// best score of their query.
+    ScoreNormalized,
+}
+
+impl Default for MergeStrategy {
+    fn default() -> Self {
+        MergeStrategy::FirstNonEmpty
+    }
+}
+
+/// Collapsing of the near-identical results of the forward geocoder, like the same
+/// street indexed once per admin, or the same stop indexed from several sources.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Dedup {
+    pub enabled: bool,
+    /// Maximum distance, in meters, between two results with the same label and type
+    /// for them to be collapsed.
+    pub max_distance: u32,
+}
+
+impl Default for Dedup {
+    fn default() -> Self {
+        Dedup {
+            enabled: false,
+            max_distance: 500,
+        }
+    }
+}
+
+/// Maximum number of results of each type in the responses of the forward geocoder,
+/// so that a type with a high boost does not hide all the others.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Quotas {
+    /// When there are quotas, or when results are collapsed, this many times the requested
+    /// number of results are fetched, for the other results to fill the response.
+    pub overfetch: u32,
+    /// Maximum number of results per type, keyed by the types of the `type[]` parameter
+    /// (house, poi, public_transport:stop_area, street, zone).
+    #[serde(default)]
+    pub max_per_type: BTreeMap<String, usize>,
+}
+
+impl Default for Quotas {
+    fn default() -> Self {
+        Quotas {
+            overfetch: 3,
+            max_per_type: BTreeMap::new(),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct QuerySettings {
+    #[serde(default)]
+    pub merge_strategy: MergeStrategy,
+    #[serde(default)]
+    pub dedup: Dedup,
+    #[serde(default)]
+    pub quotas: Quotas,
     pub type_query: TypeQueryBoosts,
     pub string_query: StringQuery,
     pub importance_query: ImportanceQueryBoosts,
@@ -128,6 +205,31 @@ impl QuerySettings {
 
         QuerySettings::new(&settings_content)
     }
+
+    /// Returns these settings, with the values given by `overrides` replaced. Objects
+    /// are merged, so overrides only need the values which differ, eg
+    /// `{"type_query": {"boosts": {"stop": 30.0}}}`.
+    pub fn with_overrides(&self, overrides: &serde_json::Value) -> Result<QuerySettings, Error> {
+        let mut settings = serde_json::to_value(self).context(InvalidOverridesSnafu)?;
+        merge_json(&mut settings, overrides);
+        serde_json::from_value(settings).context(InvalidOverridesSnafu)
+    }
+}
+
+fn merge_json(value: &mut serde_json::Value, overrides: &serde_json::Value) {
+    match (value, overrides) {
+        (serde_json::Value::Object(value), serde_json::Value::Object(overrides)) => {
+            for (key, override_value) in overrides {
+                match value.get_mut(key) {
+                    Some(value) => merge_json(value, override_value),
+                    None => {
+                        value.insert(key.clone(), override_value.clone());
+                    }
+                }
+            }
+        }
+        (value, overrides) => *value = overrides.clone(),
+    }
 }
 
 impl Default for QuerySettings {
@@ -138,6 +240,101 @@ impl Default for QuerySettings {
     }
 }
 
+/// Name of the profile made of the query settings of the `query` section.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Query settings selected for a request, and the name of their profile.
+#[derive(Clone, Debug)]
+pub struct Profile {
+    pub name: String,
+    pub settings: Arc<QuerySettings>,
+}
+
+/// A handle on the query settings used by bragi, which can be replaced while it is
+/// running. Clones of the handle share the same settings.
+///
+/// Besides the default settings, there can be named profiles, to serve different
+/// tunings of the queries from the same bragi.
+#[derive(Clone, Debug)]
+pub struct SettingsHandle {
+    active: Arc<RwLock<ActiveSettings>>,
+}
+
+#[derive(Debug)]
+struct ActiveSettings {
+    settings: Arc<QuerySettings>,
+    profiles: BTreeMap<String, Arc<QuerySettings>>,
+    hash: String,
+}
+
+impl ActiveSettings {
+    fn new(settings: QuerySettings, profiles: BTreeMap<String, QuerySettings>) -> Self {
+        let hash = settings_hash(&settings, &profiles);
+        ActiveSettings {
+            settings: Arc::new(settings),
+            profiles: profiles
+                .into_iter()
+                .map(|(name, settings)| (name, Arc::new(settings)))
+                .collect(),
+            hash,
+        }
+    }
+}
+
+impl SettingsHandle {
+    pub fn new(settings: QuerySettings, profiles: BTreeMap<String, QuerySettings>) -> Self {
+        SettingsHandle {
+            active: Arc::new(RwLock::new(ActiveSettings::new(settings, profiles))),
+        }
+    }
+
+    /// Returns the current default settings. Requests keep the settings they started
+    /// with, even if they are replaced in the meantime.
+    pub fn current(&self) -> Arc<QuerySettings> {
+        // A panic while the lock is held can't leave the settings half replaced.
+        let active = self.active.read().unwrap_or_else(|err| err.into_inner());
+        active.settings.clone()
+    }
+
+    /// Returns the current settings of the profile with the given name, or the default
+    /// settings if no name is given. Returns `None` if there is no such profile.
+    pub fn profile(&self, name: Option<&str>) -> Option<Profile> {
+        let active = self.active.read().unwrap_or_else(|err| err.into_inner());
+        match name {
+            None | Some(DEFAULT_PROFILE) => Some(Profile {
+                name: DEFAULT_PROFILE.to_string(),
+                settings: active.settings.clone(),
+            }),
+            Some(name) => active.profiles.get(name).map(|settings| Profile {
+                name: name.to_string(),
+                settings: settings.clone(),
+            }),
+        }
+    }
+
+    /// Returns the hash of the current settings and profiles, to tell which settings
+    /// are used.
+    pub fn hash(&self) -> String {
+        let active = self.active.read().unwrap_or_else(|err| err.into_inner());
+        active.hash.clone()
+    }
+
+    /// Replaces the settings and the profiles used by the following requests.
+    pub fn replace(&self, settings: QuerySettings, profiles: BTreeMap<String, QuerySettings>) {
+        let mut active = self.active.write().unwrap_or_else(|err| err.into_inner());
+        *active = ActiveSettings::new(settings, profiles);
+    }
+}
+
+// The settings are hashed through their JSON serialization, as they contain floats.
+fn settings_hash(settings: &QuerySettings, profiles: &BTreeMap<String, QuerySettings>) -> String {
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_string(&(settings, profiles))
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -147,4 +344,45 @@ mod tests {
     async fn should_get_default_query_settings() {
         let _settings = QuerySettings::default();
     }
+
+    #[test]
+    fn should_replace_the_settings_of_all_handles() {
+        let handle = SettingsHandle::new(QuerySettings::default(), BTreeMap::new());
+        let clone = handle.clone();
+        let hash = handle.hash();
+        let mut settings = QuerySettings::default();
+        settings.dedup.enabled = !settings.dedup.enabled;
+        handle.replace(settings, BTreeMap::new());
+        assert_ne!(clone.hash(), hash);
+        assert_eq!(
+            clone.current().dedup.enabled,
+            handle.current().dedup.enabled
+        );
+        assert_ne!(
+            clone.current().dedup.enabled,
+            QuerySettings::default().dedup.enabled
+        );
+    }
+
+    #[test]
+    fn should_select_profiles_with_overrides() {
+        let settings = QuerySettings::default();
+        let stops_first = settings
+            .with_overrides(&serde_json::json!({"type_query": {"boosts": {"stop": 100.0}}}))
+            .unwrap();
+        assert!(stops_first.type_query.boosts.stop > settings.type_query.boosts.stop);
+        assert!((stops_first.type_query.boosts.poi - settings.type_query.boosts.poi).abs() < 1e-9);
+        assert!(settings
+            .with_overrides(&serde_json::json!({"type_query": {"boosts": {"stop": "high"}}}))
+            .is_err());
+
+        let profiles = BTreeMap::from([("stops_first".to_string(), stops_first)]);
+        let handle = SettingsHandle::new(settings, profiles);
+        assert_eq!(handle.profile(None).unwrap().name, DEFAULT_PROFILE);
+        assert_eq!(
+            handle.profile(Some("stops_first")).unwrap().name,
+            "stops_first"
+        );
+        assert!(handle.profile(Some("unknown")).is_none());
+    }
 }
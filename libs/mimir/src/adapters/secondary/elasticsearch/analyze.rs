@@ -0,0 +1,18 @@
+use super::ElasticsearchStorage;
+use crate::domain::model::analysis::Token;
+use crate::domain::model::configuration::root_doctype;
+use crate::domain::ports::secondary::analyze::{Analyze, Error, Parameters};
+use async_trait::async_trait;
+
+#[async_trait]
+impl Analyze for ElasticsearchStorage {
+    async fn analyze(&self, parameters: Parameters) -> Result<Vec<Token>, Error> {
+        self.analyze_text(
+            root_doctype(&parameters.doc_type),
+            parameters.field,
+            parameters.text,
+        )
+        .await
+        .map_err(|err| Error::TextAnalysisError { source: err.into() })
+    }
+}
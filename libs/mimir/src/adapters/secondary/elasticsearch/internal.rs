@@ -1,14 +1,16 @@
 use elasticsearch::cat::CatIndicesParts;
 use elasticsearch::cluster::{ClusterHealthParts, ClusterPutComponentTemplateParts};
+use elasticsearch::http::request::JsonBody;
 use elasticsearch::http::response::Exception;
 use elasticsearch::indices::{
-    IndicesCreateParts, IndicesDeleteParts, IndicesForcemergeParts, IndicesGetAliasParts,
-    IndicesPutIndexTemplateParts, IndicesRefreshParts,
+    IndicesAnalyzeParts, IndicesCreateParts, IndicesDeleteParts, IndicesForcemergeParts,
+    IndicesGetAliasParts, IndicesPutIndexTemplateParts, IndicesRefreshParts,
 };
 use elasticsearch::ingest::IngestPutPipelineParts;
 use elasticsearch::params::TrackTotalHits;
 use elasticsearch::{
-    BulkOperation, BulkParts, ExplainParts, MgetParts, OpenPointInTimeParts, SearchParts,
+    BulkOperation, BulkParts, ExplainParts, MgetParts, MsearchParts, OpenPointInTimeParts,
+    SearchParts,
 };
 use futures::stream::{self, Stream, StreamExt, TryStreamExt};
 use lazy_static::lazy_static;
@@ -26,18 +28,23 @@ use tracing::info;
 use super::configuration::{
     ComponentTemplateConfiguration, Error as ConfigurationError, IndexTemplateConfiguration,
 };
-use super::models::{ElasticsearchBulkResponse, ElasticsearchSearchResponse};
+use super::models::{
+    ElasticsearchAnalyzeResponse, ElasticsearchBulkResponse, ElasticsearchSearchResponse,
+};
 use super::ElasticsearchStorage;
 use crate::adapters::secondary::elasticsearch::models::{
-    ElasticsearchBulkResult, ElasticsearchGetResponse,
+    ElasticsearchBulkResult, ElasticsearchGetResponse, ElasticsearchMultiSearchResponse,
 };
 use crate::domain::model::{
+    analysis::Token,
     configuration,
+    hit::Hit,
     index::{Index, IndexStatus},
     query::Query,
     stats::InsertStats as ModelInsertStats,
     status::{StorageHealth, Version as StorageVersion},
 };
+use crate::domain::ports::secondary::search::Parameters as SearchParameters;
 use common::document::Document;
 
 #[derive(Debug, Snafu)]
@@ -1056,19 +1063,7 @@ impl ElasticsearchStorage {
         D: DeserializeOwned + Send + Sync + 'static,
     {
         let indices = indices.iter().map(String::as_str).collect::<Vec<_>>();
-        let timeout = timeout
-            .map(|t| {
-                if t > self.config.timeout {
-                    info!(
-                        "Requested timeout {:?} is too big. I'll use {:?} instead.",
-                        t, self.config.timeout
-                    );
-                    self.config.timeout
-                } else {
-                    t
-                }
-            }) // let's cap the timeout to self.config.timeout to prevent overloading elasticsearch with long requests
-            .unwrap_or(self.config.timeout);
+        let timeout = self.cap_timeout(timeout);
         let shard_timeout = format!("{}ms", timeout.as_millis());
         let request_timeout = timeout.saturating_add(timeout);
 
@@ -1130,15 +1125,89 @@ impl ElasticsearchStorage {
         }
     }
 
-    pub(super) async fn get_documents_by_id<D>(
+    /// Sends all the searches in a single request to Elasticsearch's multi search API.
+    pub(super) async fn multi_search_documents<D>(
         &self,
-        query: Query,
-        timeout: Option<Duration>,
-    ) -> Result<Vec<D>, Error>
+        searches: Vec<SearchParameters>,
+    ) -> Result<Vec<Vec<Hit<D>>>, Error>
     where
         D: DeserializeOwned + Send + Sync + 'static,
     {
-        let timeout = timeout
+        // The request must not last longer than the longest of its searches.
+        let timeout = searches
+            .iter()
+            .map(|search| self.cap_timeout(search.timeout))
+            .max()
+            .unwrap_or(self.config.timeout);
+        let request_timeout = timeout.saturating_add(timeout);
+
+        // The body of a multi search is made of pairs of lines: a header, with the indices
+        // to search in, followed by the search itself.
+        let mut body: Vec<JsonBody<Value>> = Vec::with_capacity(2 * searches.len());
+        for search in searches {
+            let shard_timeout = format!("{}ms", self.cap_timeout(search.timeout).as_millis());
+            let mut query = match search.query {
+                Query::QueryString(q) => json!({ "query": { "query_string": { "query": q } } }),
+                Query::QueryDSL(json) => json,
+            };
+            if let Some(query) = query.as_object_mut() {
+                query.insert("size".to_string(), json!(search.result_limit));
+                query.insert("timeout".to_string(), json!(shard_timeout));
+                // we don't care for the total number of hits, and it takes some time to compute
+                query.insert("track_total_hits".to_string(), json!(false));
+            }
+            body.push(json!({ "index": search.es_indices_to_search_in }).into());
+            body.push(query.into());
+        }
+
+        let response = self
+            .client
+            .msearch(MsearchParts::None)
+            .request_timeout(request_timeout)
+            .body(body)
+            .send()
+            .await
+            .context(ElasticsearchClientSnafu {
+                details: "could not run multi search".to_string(),
+            })?;
+
+        if response.status_code().is_success() {
+            let body = response
+                .json::<ElasticsearchMultiSearchResponse<D>>()
+                .await
+                .context(ElasticsearchDeserializationSnafu)?;
+
+            body.responses
+                .into_iter()
+                .map(|item| match (item.hits, item.error) {
+                    (Some(hits), _) => Ok(hits
+                        .hits
+                        .into_iter()
+                        .map(|hit| Hit {
+                            document: hit.source,
+                            score: hit.score,
+                            explanation: hit.explanation,
+                        })
+                        .collect()),
+                    (None, error) => Err(Error::ElasticsearchUnhandledException {
+                        details: error
+                            .map(|error| error.to_string())
+                            .unwrap_or_else(|| String::from("Unspecified multi search error")),
+                    }),
+                })
+                .collect()
+        } else {
+            Err(response
+                .exception()
+                .await
+                .expect("failed to fetch Elasticsearch exception")
+                .into())
+        }
+    }
+
+    // let's cap the timeout to self.config.timeout to prevent overloading elasticsearch with long requests
+    fn cap_timeout(&self, timeout: Option<Duration>) -> Duration {
+        timeout
             .map(|t| {
                 if t > self.config.timeout {
                     info!(
@@ -1149,8 +1218,19 @@ impl ElasticsearchStorage {
                 } else {
                     t
                 }
-            }) // let's cap the timeout to self.config.timeout to prevent overloading elasticsearch with long requests
-            .unwrap_or(self.config.timeout);
+            })
+            .unwrap_or(self.config.timeout)
+    }
+
+    pub(super) async fn get_documents_by_id<D>(
+        &self,
+        query: Query,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<D>, Error>
+    where
+        D: DeserializeOwned + Send + Sync + 'static,
+    {
+        let timeout = self.cap_timeout(timeout);
 
         let get = self.client.mget(MgetParts::None).request_timeout(timeout);
 
@@ -1253,6 +1333,59 @@ impl ElasticsearchStorage {
         }
     }
 
+    /// Analyzes a text with the analyzer of a field of the indices `<index>_*`.
+    ///
+    /// The analyzers are defined by the templates, so they are the same for all the
+    /// datasets, and the text is analyzed by an arbitrary index among them.
+    pub(super) async fn analyze_text(
+        &self,
+        index: String,
+        field: String,
+        text: String,
+    ) -> Result<Vec<Token>, Error> {
+        // Elasticsearch refuses to analyze a text with an alias pointing to several indices.
+        let index = self
+            .find_aliases(index.clone())
+            .await?
+            .into_keys()
+            .next()
+            .ok_or(Error::ElasticsearchUnknownIndex { index })?;
+
+        let response = self
+            .client
+            .indices()
+            .analyze(IndicesAnalyzeParts::Index(&index))
+            .request_timeout(self.config.timeout)
+            .body(json!({ "field": field, "text": text }))
+            .send()
+            .await
+            .context(ElasticsearchClientSnafu {
+                details: format!(
+                    "could not analyze text with field {} of index {}",
+                    field, index
+                ),
+            })?;
+
+        if response.status_code().is_success() {
+            let json = response
+                .json::<Value>()
+                .await
+                .context(ElasticsearchDeserializationSnafu)?;
+            let analysis = serde_json::from_value::<ElasticsearchAnalyzeResponse>(json).context(
+                JsonDeserializationSnafu {
+                    details: String::from("could not deserialize analysis"),
+                },
+            )?;
+            Ok(analysis.tokens)
+        } else {
+            Err(response
+                .exception()
+                .await
+                .expect("failed to fetch Elasticsearch exception")
+                .into())
+        }
+    }
+
     pub(super) async fn cluster_health(&self) -> Result<StorageHealth, Error> {
         let response = self
             .client
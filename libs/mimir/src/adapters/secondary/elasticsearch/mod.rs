@@ -5,6 +5,7 @@ use std::path::PathBuf;
 use std::time::Duration;
 use url::Url;
 
+pub mod analyze;
 pub mod configuration;
 pub mod explain;
 pub(super) mod internal;
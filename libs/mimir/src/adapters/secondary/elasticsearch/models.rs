@@ -3,6 +3,9 @@
 use serde::{Deserialize, Deserializer};
 use serde_json::Value;
 
+use crate::domain::model::analysis::Token;
+use crate::domain::model::explanation::Explanation;
+
 /// ES response for a search query.
 #[derive(Deserialize)]
 pub struct ElasticsearchSearchResponse<D> {
@@ -19,6 +22,11 @@ pub struct ElasticsearchHits<D> {
 pub struct ElasticsearchHit<D> {
     #[serde(rename = "_source")]
     pub source: D,
+    #[serde(rename = "_score", default)]
+    pub score: Option<f64>,
+    /// Only given when the search is explained.
+    #[serde(rename = "_explanation", default)]
+    pub explanation: Option<Explanation>,
     #[serde(default)]
     pub sort: Vec<Value>,
 }
@@ -30,6 +38,19 @@ impl<D> ElasticsearchSearchResponse<D> {
     }
 }
 
+/// ES response for a multi search query.
+#[derive(Deserialize)]
+pub struct ElasticsearchMultiSearchResponse<D> {
+    pub responses: Vec<ElasticsearchMultiSearchItem<D>>,
+}
+
+/// Each search of a multi search either succeeds with hits, or fails with an error.
+#[derive(Deserialize)]
+pub struct ElasticsearchMultiSearchItem<D> {
+    pub hits: Option<ElasticsearchHits<D>>,
+    pub error: Option<Value>,
+}
+
 /// ES response for a get query.
 #[derive(Deserialize)]
 pub struct ElasticsearchGetResponse<D> {
@@ -49,6 +70,12 @@ impl<D> ElasticsearchGetResponse<D> {
     }
 }
 
+/// ES response for an analyze query.
+#[derive(Deserialize)]
+pub struct ElasticsearchAnalyzeResponse {
+    pub tokens: Vec<Token>,
+}
+
 /// ES response for bulk insert queries.
 #[derive(Debug, Eq, PartialEq, Deserialize)]
 pub struct ElasticsearchBulkResponse {
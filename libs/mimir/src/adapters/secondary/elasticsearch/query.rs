@@ -1,6 +1,7 @@
 use async_trait::async_trait;
 
 use super::ElasticsearchStorage;
+use crate::domain::model::hit::Hit;
 use crate::domain::ports::secondary::get::{Error as GetError, Get, Parameters as GetParameters};
 use crate::domain::ports::secondary::search::{
     Error as SearchError, Parameters as SearchParameters, Search,
@@ -23,6 +24,15 @@ impl Search for ElasticsearchStorage {
         .await
         .map_err(|err| SearchError::DocumentRetrievalError { source: err.into() })
     }
+
+    async fn multi_search_documents(
+        &self,
+        parameters: Vec<SearchParameters>,
+    ) -> Result<Vec<Vec<Hit<Self::Doc>>>, SearchError> {
+        self.multi_search_documents(parameters)
+            .await
+            .map_err(|err| SearchError::DocumentRetrievalError { source: err.into() })
+    }
 }
 
 #[async_trait]
@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+/// A token produced by the analysis of a text, as it is indexed or searched.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    /// text of the token
+    pub token: String,
+    /// offset of the first character of the token in the analyzed text
+    pub start_offset: usize,
+    /// offset following the last character of the token in the analyzed text
+    pub end_offset: usize,
+    /// position of the token in the stream: synonyms share the position of the word they replace
+    pub position: usize,
+    /// kind of token, like '<ALPHANUM>' or 'SYNONYM'
+    #[serde(rename = "type")]
+    pub token_type: String,
+}
@@ -11,6 +11,9 @@ pub enum Error {
     #[snafu(display("Document Retrieval Error: {}", source))]
     DocumentRetrievalError { source: Box<dyn std::error::Error> },
 
+    #[snafu(display("Text Analysis Error: {}", source))]
+    TextAnalysis { source: Box<dyn std::error::Error> },
+
     #[snafu(display("Index Creation Error: {}", source))]
     IndexCreation { source: Box<dyn std::error::Error> },
 
@@ -12,3 +12,13 @@ pub struct Explanation {
     /// leafs
     pub details: Vec<Explanation>,
 }
+
+impl From<Explanation> for places::context::Explanation {
+    fn from(explanation: Explanation) -> Self {
+        places::context::Explanation {
+            value: explanation.value,
+            description: explanation.description,
+            details: explanation.details.into_iter().map(Into::into).collect(),
+        }
+    }
+}
@@ -0,0 +1,12 @@
+use super::explanation::Explanation;
+
+/// A document found by a search, along with the score assigned by the backend.
+#[derive(Debug, Clone)]
+pub struct Hit<D> {
+    pub document: D,
+    /// Relevance of the document for the query. Scores are only comparable
+    /// between documents returned by the same query.
+    pub score: Option<f64>,
+    /// How the backend computed the score, if the search was explained.
+    pub explanation: Option<Explanation>,
+}
@@ -1,6 +1,8 @@
+pub mod analysis;
 pub mod configuration;
 pub mod error;
 pub mod explanation;
+pub mod hit;
 pub mod index;
 pub mod query;
 pub mod stats;
@@ -0,0 +1,36 @@
+use crate::domain::model::{analysis::Token, error::Error as ModelError};
+use crate::domain::ports::secondary::analyze::{Analyze, Parameters};
+use async_trait::async_trait;
+
+#[async_trait]
+pub trait AnalyzeText {
+    async fn analyze_text(
+        &self,
+        text: String,
+        field: String,
+        doc_type: String,
+    ) -> Result<Vec<Token>, ModelError>;
+}
+
+#[async_trait]
+impl<T> AnalyzeText for T
+where
+    T: Analyze + Send + Sync,
+{
+    async fn analyze_text(
+        &self,
+        text: String,
+        field: String,
+        doc_type: String,
+    ) -> Result<Vec<Token>, ModelError> {
+        let analyze_params = Parameters {
+            doc_type,
+            field,
+            text,
+        };
+
+        self.analyze(analyze_params)
+            .await
+            .map_err(|err| ModelError::TextAnalysis { source: err.into() })
+    }
+}
@@ -6,12 +6,6 @@ use serde::de::DeserializeOwned;
 use crate::domain::model::{error::Error as ModelError, query::Query};
 use crate::domain::ports::secondary::get::{Get, Parameters};
 
-// automock requires that the associated type be known at
-// compile time. So here we import admins when we are in
-// testing configuration.
-#[cfg(test)]
-use places::admin::Admin;
-
 #[async_trait]
 pub trait GetDocuments {
     type Document;
@@ -1,3 +1,4 @@
+pub mod analyze_text;
 pub mod configure_backend;
 pub mod explain_query;
 pub mod generate_index;
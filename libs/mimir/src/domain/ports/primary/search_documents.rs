@@ -3,15 +3,9 @@ use std::time::Duration;
 use async_trait::async_trait;
 use serde::de::DeserializeOwned;
 
-use crate::domain::model::{error::Error as ModelError, query::Query};
+use crate::domain::model::{error::Error as ModelError, hit::Hit, query::Query};
 use crate::domain::ports::secondary::search::{Parameters, Search};
 
-// automock requires that the associated type be known at
-// compile time. So here we import admins when we are in
-// testing configuration.
-#[cfg(test)]
-use places::admin::Admin;
-
 #[async_trait]
 pub trait SearchDocuments {
     type Document;
@@ -23,6 +17,16 @@ pub trait SearchDocuments {
         result_limit: i64,
         timeout: Option<Duration>,
     ) -> Result<Vec<Self::Document>, ModelError>;
+
+    /// Run each query on the same indices in a single round-trip, and return the
+    /// hits of each query, in the order of `queries`.
+    async fn multi_search_documents(
+        &self,
+        es_indices_to_search_in: Vec<String>,
+        queries: Vec<Query>,
+        result_limit: i64,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<Vec<Hit<Self::Document>>>, ModelError>;
 }
 
 #[async_trait]
@@ -49,4 +53,25 @@ where
         .await
         .map_err(|err| ModelError::DocumentRetrievalError { source: err.into() })
     }
+
+    async fn multi_search_documents(
+        &self,
+        es_indices_to_search_in: Vec<String>,
+        queries: Vec<Query>,
+        result_limit: i64,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<Vec<Hit<Self::Document>>>, ModelError> {
+        let parameters = queries
+            .into_iter()
+            .map(|query| Parameters {
+                es_indices_to_search_in: es_indices_to_search_in.clone(),
+                query,
+                result_limit,
+                timeout,
+            })
+            .collect();
+        self.multi_search_documents(parameters)
+            .await
+            .map_err(|err| ModelError::DocumentRetrievalError { source: err.into() })
+    }
 }
@@ -0,0 +1,23 @@
+use crate::domain::model::analysis::Token;
+use async_trait::async_trait;
+use snafu::Snafu;
+
+#[derive(Debug, Clone)]
+pub struct Parameters {
+    pub doc_type: String,
+    pub field: String,
+    pub text: String,
+}
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Text Analysis Error: {}", source))]
+    TextAnalysisError { source: Box<dyn std::error::Error> },
+}
+
+/// This port defines a method to debug how the text of queries / documents is analyzed
+#[async_trait]
+pub trait Analyze {
+    /// Returns the tokens obtained with the analyzer of a field of the documents of a given type.
+    async fn analyze(&self, parameters: Parameters) -> Result<Vec<Token>, Error>;
+}
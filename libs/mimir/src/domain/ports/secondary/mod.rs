@@ -1,3 +1,4 @@
+pub mod analyze;
 pub mod explain;
 pub mod get;
 pub mod list;
@@ -5,7 +5,7 @@ use async_trait::async_trait;
 use serde::de::DeserializeOwned;
 use snafu::Snafu;
 
-use crate::domain::model::query::Query;
+use crate::domain::model::{hit::Hit, query::Query};
 
 #[derive(Debug, Clone)]
 pub struct Parameters {
@@ -26,4 +26,12 @@ pub enum Error {
 pub trait Search {
     type Doc: DeserializeOwned + Send + Sync + 'static;
     async fn search_documents(&self, parameters: Parameters) -> Result<Vec<Self::Doc>, Error>;
+
+    /// Run several searches in a single round-trip to the backend.
+    ///
+    /// The result contains the hits of each search, in the order of `parameters`.
+    async fn multi_search_documents(
+        &self,
+        parameters: Vec<Parameters>,
+    ) -> Result<Vec<Vec<Hit<Self::Doc>>>, Error>;
 }
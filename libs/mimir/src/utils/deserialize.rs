@@ -17,3 +17,24 @@ where
     let ms: u64 = Deserialize::deserialize(deserializer)?;
     Ok(Some(Duration::from_millis(ms)))
 }
+
+/// Deserializes a comma separated list of values, eg "fr,be". An empty list is `None`.
+pub fn deserialize_opt_comma_separated<'de, D>(
+    deserializer: D,
+) -> Result<Option<Vec<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: String = Deserialize::deserialize(deserializer)?;
+    let values: Vec<String> = s
+        .split(',')
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+        .collect();
+    Ok(if values.is_empty() {
+        None
+    } else {
+        Some(values)
+    })
+}
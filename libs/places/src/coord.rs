@@ -1,4 +1,5 @@
 use geojson::Geometry;
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
 use serde::de::{self, Deserializer, MapAccess, SeqAccess, Visitor};
 use serde::ser::SerializeStruct;
 use serde::Deserialize;
@@ -56,6 +57,23 @@ impl serde::Serialize for Coord {
     }
 }
 
+// The schema follows the custom serialization above.
+impl JsonSchema for Coord {
+    fn schema_name() -> String {
+        "Coord".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        #[derive(JsonSchema)]
+        #[allow(dead_code)]
+        struct LonLat {
+            lon: f64,
+            lat: f64,
+        }
+        LonLat::json_schema(gen)
+    }
+}
+
 impl From<Coord> for Geometry {
     fn from(coord: Coord) -> Geometry {
         Geometry::new(geojson::Value::Point(vec![coord.lon(), coord.lat()]))
@@ -1,5 +1,6 @@
 use common::document::{ContainerDocument, Document};
 use geojson::Geometry;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::sync::Arc;
@@ -45,7 +46,7 @@ pub struct Poi {
     pub context: Option<Context>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
 pub struct PoiType {
     pub id: String,
     pub name: String,
@@ -1,4 +1,5 @@
 use geojson::Geometry;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::collections::BTreeMap;
@@ -14,33 +15,43 @@ use crate::admin::Admin;
 use crate::utils::normalize_id;
 use common::document::{ContainerDocument, Document};
 
-#[derive(Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd, JsonSchema,
+)]
 pub struct CommercialMode {
     pub id: String,
     pub name: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd, JsonSchema,
+)]
 pub struct PhysicalMode {
     pub id: String,
     pub name: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd, JsonSchema,
+)]
 pub struct Network {
     pub id: String,
     pub name: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Default)]
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Default, JsonSchema,
+)]
 pub struct Line {
     pub id: String,
     pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub code: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<String>")]
     pub color: Option<Rgb>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<String>")]
     pub text_color: Option<Rgb>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub commercial_mode: Option<CommercialMode>,
@@ -94,7 +105,9 @@ impl FromTransitModel<transit_model::objects::Line> for Line {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd, JsonSchema,
+)]
 pub struct FeedPublisher {
     pub id: String,
     pub license: String,
@@ -102,7 +115,7 @@ pub struct FeedPublisher {
     pub url: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct Comment {
     pub name: String,
 }
@@ -0,0 +1,242 @@
+use clap::Parser;
+use mimir::adapters::primary::bragi::handlers;
+use mimir::adapters::primary::bragi::query_log::{returned_ids, QueryLogEntry};
+use mimir::adapters::primary::bragi::routes::PROFILE_HEADER;
+use mimir::adapters::primary::common::settings::QuerySettings;
+use mimir::adapters::secondary::elasticsearch::{
+    remote::connection_pool_url, ElasticsearchStorage, ElasticsearchStorageConfig,
+};
+use mimir::domain::ports::secondary::remote::Remote;
+use serde_json::json;
+use snafu::{ResultExt, Snafu};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Cannot read the query log {}: {}", path.display(), source))]
+    QueryLog {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("Config Compilation Error: {}", source))]
+    ConfigCompilation { source: common::config::Error },
+
+    #[snafu(display("Config Value Error: {} [{}]", msg, source))]
+    ConfigValue {
+        msg: String,
+        source: config::ConfigError,
+    },
+
+    #[snafu(display("Elasticsearch Connection Pool {}", source))]
+    ElasticsearchConnection {
+        source: mimir::domain::ports::secondary::remote::Error,
+    },
+
+    #[snafu(display("Invalid Query Parameters: {}", details))]
+    InvalidParameters { details: String },
+
+    #[snafu(display("Bragi Request Error: {}", source))]
+    BragiRequest { source: reqwest::Error },
+
+    #[snafu(display("Search Error: {}", info))]
+    Search { info: String },
+}
+
+#[derive(Debug, Parser)]
+#[clap(
+    name = "replay",
+    about = "Replays a bragi query log, and compares the ids returned with those of the log"
+)]
+struct Opts {
+    /// Query log to replay, as written by bragi
+    #[clap(parse(from_os_str))]
+    log: PathBuf,
+
+    /// Number of ids compared for each query, from the first one
+    #[clap(short = 'n', long = "top", default_value = "5")]
+    top: usize,
+
+    #[clap(subcommand)]
+    target: Target,
+}
+
+#[derive(Debug, clap::Parser)]
+enum Target {
+    /// Sends the queries to a running bragi
+    Bragi {
+        /// Base url of bragi, like 'http://localhost:5000'
+        url: String,
+    },
+    /// Builds the queries with the query settings of a configuration, and sends them
+    /// directly to Elasticsearch
+    Elasticsearch {
+        /// Defines the config directory
+        ///
+        /// This directory must contain 'elasticsearch' and 'query' subdirectories.
+        #[clap(parse(from_os_str), short = 'c', long = "config-dir")]
+        config_dir: PathBuf,
+
+        /// Defines the run mode in {testing, dev, prod, ...}
+        #[clap(short = 'm', long = "run-mode")]
+        run_mode: Option<String>,
+
+        /// Override settings values using key=value, like 'query.dedup.enabled=true'
+        #[clap(
+            short = 's',
+            long = "setting",
+            multiple_values = false,
+            multiple_occurrences = true
+        )]
+        settings: Vec<String>,
+    },
+}
+
+/// Runs the queries of the log again.
+#[allow(clippy::large_enum_variant)]
+enum Replayer {
+    Bragi {
+        client: reqwest::Client,
+        url: String,
+    },
+    Elasticsearch {
+        client: ElasticsearchStorage,
+        settings: QuerySettings,
+        timeout: Duration,
+    },
+}
+
+impl Replayer {
+    async fn new(target: Target) -> Result<Self, Error> {
+        match target {
+            Target::Bragi { url } => Ok(Replayer::Bragi {
+                client: reqwest::Client::new(),
+                url: url.trim_end_matches('/').to_string(),
+            }),
+            Target::Elasticsearch {
+                config_dir,
+                run_mode,
+                settings,
+            } => {
+                let config = common::config::config_from(
+                    config_dir.as_ref(),
+                    &["elasticsearch", "query"],
+                    run_mode.as_deref(),
+                    "BRAGI",
+                    settings,
+                )
+                .context(ConfigCompilationSnafu)?;
+                let elasticsearch: ElasticsearchStorageConfig =
+                    config.get("elasticsearch").context(ConfigValueSnafu {
+                        msg: "elasticsearch",
+                    })?;
+                let settings: QuerySettings = config
+                    .get("query")
+                    .context(ConfigValueSnafu { msg: "query" })?;
+                let timeout = elasticsearch.timeout;
+                let client = connection_pool_url(&elasticsearch.url)
+                    .conn(elasticsearch)
+                    .await
+                    .context(ElasticsearchConnectionSnafu)?;
+                Ok(Replayer::Elasticsearch {
+                    client,
+                    settings,
+                    timeout,
+                })
+            }
+        }
+    }
+
+    /// Returns the ids of the places found for a logged query.
+    async fn ids(&self, entry: &QueryLogEntry) -> Result<Vec<String>, Error> {
+        match self {
+            Replayer::Bragi { client, url } => {
+                let json: serde_json::Value = client
+                    .get(format!("{}/api/v1/autocomplete?{}", url, entry.params))
+                    .header(PROFILE_HEADER, &entry.profile)
+                    .send()
+                    .await
+                    .context(BragiRequestSnafu)?
+                    .json()
+                    .await
+                    .context(BragiRequestSnafu)?;
+                Ok(returned_ids(&json))
+            }
+            Replayer::Elasticsearch {
+                client,
+                settings,
+                timeout,
+            } => {
+                let params =
+                    entry
+                        .forward_geocoder_query()
+                        .map_err(|err| Error::InvalidParameters {
+                            details: err.to_string(),
+                        })?;
+                let langs: Vec<String> = params.lang.iter().cloned().collect();
+                let places = handlers::forward_geocode_places(
+                    client, params, None, settings, *timeout, &langs,
+                )
+                .await
+                .map_err(|err| Error::Search { info: err.info })?;
+                Ok(handlers::forward_geocode_features(places, &langs)
+                    .into_iter()
+                    .map(|feature| feature.properties.geocoding.id)
+                    .collect())
+            }
+        }
+    }
+}
+
+/// Prints, as NDJSON, the queries whose first ids changed, and a summary on stderr.
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let opts = Opts::parse();
+    let replayer = Replayer::new(opts.target).await?;
+    let log = File::open(&opts.log).context(QueryLogSnafu { path: &opts.log })?;
+
+    let (mut replayed, mut changed, mut failed) = (0, 0, 0);
+    for line in BufReader::new(log).lines() {
+        let line = line.context(QueryLogSnafu { path: &opts.log })?;
+        let entry: QueryLogEntry = match serde_json::from_str(&line) {
+            Ok(entry) => entry,
+            Err(err) => {
+                eprintln!("Skipping invalid query log entry: {}", err);
+                failed += 1;
+                continue;
+            }
+        };
+        let ids = match replayer.ids(&entry).await {
+            Ok(ids) => ids,
+            Err(err) => {
+                eprintln!("Cannot replay '{}': {}", entry.params, err);
+                failed += 1;
+                continue;
+            }
+        };
+        replayed += 1;
+        let before = &entry.ids[..opts.top.min(entry.ids.len())];
+        let after = &ids[..opts.top.min(ids.len())];
+        if before != after {
+            changed += 1;
+            println!(
+                "{}",
+                json!({
+                    "params": entry.params,
+                    "profile": entry.profile,
+                    "before": before,
+                    "after": after,
+                })
+            );
+        }
+    }
+
+    eprintln!(
+        "{} queries replayed, {} with different top {} ids, {} failed",
+        replayed, changed, opts.top, failed
+    );
+    Ok(())
+}
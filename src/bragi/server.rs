@@ -2,16 +2,18 @@ use mimirsbrunn::utils::logger::logger_init;
 use snafu::{ResultExt, Snafu};
 use std::net::ToSocketAddrs;
 use tokio::runtime;
-use tracing::{info, instrument};
+use tracing::{info, instrument, warn};
 use warp::Filter;
 
 use super::settings::{Error as SettingsError, Opts, Settings};
 use mimir::adapters::primary::bragi::prometheus_handler::update_metrics;
 use mimir::{
     adapters::primary::bragi::api::{
-        features, forward_geocoder, forward_geocoder_explain, reverse_geocoder, status,
+        analyze, codes, features, features_batch, forward_geocoder, forward_geocoder_batch,
+        forward_geocoder_explain, graphql, nearby, openapi, reverse_geocoder, status, zones,
     },
-    adapters::primary::bragi::{handlers, routes},
+    adapters::primary::bragi::{gql, handlers, query_log, routes},
+    adapters::primary::common::settings::SettingsHandle,
     adapters::secondary::elasticsearch::remote::connection_pool_url,
     domain::ports::secondary::remote::{Error as PortRemoteError, Remote},
     metrics,
@@ -35,6 +37,9 @@ pub enum Error {
     #[snafu(display("Addr Resolution Error {}", msg))]
     AddrResolution { msg: String },
 
+    #[snafu(display("Could not open the query log: {}", source))]
+    QueryLog { source: std::io::Error },
+
     #[snafu(display("Could not init logger: {}", source))]
     InitLog {
         source: mimirsbrunn::utils::logger::Error,
@@ -52,7 +57,7 @@ pub fn run(opts: &Opts) -> Result<(), Error> {
         .build()
         .expect("Failed to build tokio runtime.");
 
-    runtime.block_on(run_server(settings))
+    runtime.block_on(run_server(settings, opts.clone()))
 }
 
 pub fn config(opts: &Opts) -> Result<(), Error> {
@@ -61,8 +66,10 @@ pub fn config(opts: &Opts) -> Result<(), Error> {
     Ok(())
 }
 
-#[instrument(skip(settings))]
-pub async fn run_server(settings: Settings) -> Result<(), Error> {
+/// Serves bragi. The query settings are reloaded from the configuration given by `opts`
+/// when bragi receives SIGHUP.
+#[instrument(skip(settings, opts))]
+pub async fn run_server(settings: Settings, opts: Opts) -> Result<(), Error> {
     info!(
         "Connecting to Elasticsearch at {}",
         &settings.elasticsearch.url
@@ -73,26 +80,102 @@ pub async fn run_server(settings: Settings) -> Result<(), Error> {
         .await
         .context(ElasticsearchConnectionSnafu)?;
 
+    let query_settings = SettingsHandle::new(
+        settings.query.clone(),
+        settings.query_profiles().context(SettingsProcessingSnafu)?,
+    );
+    info!("Using query settings {}", query_settings.hash());
+    #[cfg(unix)]
+    tokio::spawn(reload_on_hangup(opts, query_settings.clone()));
+
+    let query_log = settings
+        .query_log
+        .as_deref()
+        .map(query_log::QueryLog::create)
+        .transpose()
+        .context(QueryLogSnafu)?;
+
     // Here I place reverse_geocoder first because its most likely to get hit.
     let api = reverse_geocoder!(
         client.clone(),
-        settings.query.clone(),
-        settings.reverse_timeout
+        query_settings.clone(),
+        settings.reverse_timeout,
+        settings.reverse_max_radius,
+        settings.fallback_langs.clone()
     )
     .or(forward_geocoder!(
         client.clone(),
-        settings.query.clone(),
-        settings.autocomplete_timeout
+        query_settings.clone(),
+        settings.autocomplete_timeout,
+        settings.fallback_langs.clone()
+    ))
+    .or(forward_geocoder_batch!(
+        client.clone(),
+        query_settings.clone(),
+        settings.autocomplete_timeout,
+        settings.batch_max_size,
+        settings.batch_concurrency,
+        settings.fallback_langs.clone()
+    ))
+    .or(zones!(
+        client.clone(),
+        settings.reverse_timeout,
+        settings.fallback_langs.clone()
+    ))
+    .or(nearby!(
+        client.clone(),
+        settings.reverse_timeout,
+        settings.reverse_max_radius,
+        settings.nearby_max_limit,
+        settings.fallback_langs.clone()
+    ))
+    .or(codes!(
+        client.clone(),
+        settings.features_timeout,
+        settings.fallback_langs.clone()
+    ))
+    .or(features!(
+        client.clone(),
+        settings.features_timeout,
+        settings.fallback_langs.clone()
+    ))
+    .or(features_batch!(
+        client.clone(),
+        settings.features_timeout,
+        settings.features_max_ids,
+        settings.fallback_langs.clone()
     ))
-    .or(features!(client.clone(), settings.features_timeout))
     .or(forward_geocoder_explain!(
         client.clone(),
-        settings.query,
-        settings.autocomplete_timeout
+        query_settings.clone(),
+        settings.autocomplete_timeout,
+        settings.fallback_langs.clone()
     ))
-    .or(status!(client.clone(), &settings.elasticsearch.url))
+    .or(analyze!(client.clone(), settings.analyze_enabled))
+    .or(graphql!(
+        client.clone(),
+        query_settings.clone(),
+        gql::Timeouts {
+            autocomplete: settings.autocomplete_timeout,
+            reverse: settings.reverse_timeout,
+            features: settings.features_timeout,
+        },
+        gql::Limits {
+            reverse_max_radius: settings.reverse_max_radius,
+        },
+        settings.fallback_langs.clone()
+    ))
+    .or(status!(
+        client.clone(),
+        &settings.elasticsearch.url,
+        query_settings
+    ))
+    .or(openapi!())
     .or(metrics!())
     .recover(routes::report_invalid)
+    .with(warp::wrap_fn(move |filter| {
+        query_log::query_log_filter(filter, query_log.clone())
+    }))
     .with(warp::wrap_fn(|filter| {
         routes::cache_filter(filter, settings.http_cache_duration)
     }))
@@ -125,3 +208,34 @@ pub async fn run_server(settings: Settings) -> Result<(), Error> {
 
     Ok(())
 }
+
+/// Reloads the query settings and their profiles each time bragi receives SIGHUP.
+///
+/// The configuration is read the same way as when bragi starts, so that the run mode and
+/// the overridden values still apply. Invalid settings are reported, and the current
+/// ones are kept.
+#[cfg(unix)]
+async fn reload_on_hangup(opts: Opts, query_settings: SettingsHandle) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(err) => {
+            warn!("Cannot reload the query settings on SIGHUP: {}", err);
+            return;
+        }
+    };
+    while hangups.recv().await.is_some() {
+        let reloaded = Settings::new(&opts).and_then(|settings| {
+            let profiles = settings.query_profiles()?;
+            Ok((settings.query, profiles))
+        });
+        match reloaded {
+            Ok((settings, profiles)) => {
+                query_settings.replace(settings, profiles);
+                info!("Reloaded query settings {}", query_settings.hash());
+            }
+            Err(err) => warn!("Keeping the current query settings: {}", err),
+        }
+    }
+}
@@ -3,11 +3,14 @@ use mimir::utils::deserialize::deserialize_duration;
 use serde::{Deserialize, Serialize};
 use snafu::ResultExt;
 use snafu::Snafu;
+use std::collections::BTreeMap;
 use std::env;
 use std::path::PathBuf;
 use std::time::Duration;
 
-use mimir::adapters::primary::common::settings::QuerySettings;
+use mimir::adapters::primary::common::settings::{
+    Error as QuerySettingsError, QuerySettings, DEFAULT_PROFILE,
+};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const AUTHORS: &str = env!("CARGO_PKG_AUTHORS");
@@ -38,6 +41,15 @@ pub enum Error {
 
     #[snafu(display("Config Compilation Error: {}", source))]
     ConfigCompilation { source: common::config::Error },
+
+    #[snafu(display("Reserved Profile Name: {}", name))]
+    ReservedProfileName { name: String },
+
+    #[snafu(display("Query Profile Error: {} [{}]", name, source))]
+    QueryProfile {
+        name: String,
+        source: QuerySettingsError,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -64,9 +76,30 @@ pub struct Settings {
     pub reverse_timeout: Duration,
     #[serde(deserialize_with = "deserialize_duration")]
     pub features_timeout: Duration,
+    pub batch_max_size: usize,
+    pub batch_concurrency: usize,
+    /// Maximum number of ids in a single request to the features endpoint.
+    pub features_max_ids: usize,
+    /// Maximum search radius of a reverse geocoding or nearby request, in meters.
+    pub reverse_max_radius: u32,
+    /// Maximum number of places returned by a single request to the nearby endpoint.
+    pub nearby_max_limit: i64,
+    /// Languages of names and labels, used after those requested by the client.
+    #[serde(default)]
+    pub fallback_langs: Vec<String>,
+    /// Named query settings profiles, given by the values they override in the
+    /// query settings.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, serde_json::Value>,
+    /// Whether the analyze endpoint, which exposes how the documents are indexed, is served.
+    #[serde(default)]
+    pub analyze_enabled: bool,
+    /// File to which the autocomplete queries are appended, as NDJSON. No query log by default.
+    #[serde(default)]
+    pub query_log: Option<PathBuf>,
 }
 
-#[derive(Debug, clap::Parser)]
+#[derive(Debug, Clone, clap::Parser)]
 #[clap(
     name = "bragi",
     about = "REST API for querying Elasticsearch",
@@ -99,7 +132,7 @@ pub struct Opts {
     pub cmd: Command,
 }
 
-#[derive(Debug, clap::Parser)]
+#[derive(Debug, Clone, clap::Parser)]
 pub enum Command {
     /// Execute osm2mimir with the given configuration
     Run,
@@ -122,6 +155,23 @@ impl Settings {
             msg: "cannot merge bragi settings",
         })
     }
+
+    /// Returns the query settings of each named profile.
+    pub fn query_profiles(&self) -> Result<BTreeMap<String, QuerySettings>, Error> {
+        self.profiles
+            .iter()
+            .map(|(name, overrides)| {
+                if name == DEFAULT_PROFILE {
+                    return Err(Error::ReservedProfileName { name: name.clone() });
+                }
+                let settings = self
+                    .query
+                    .with_overrides(overrides)
+                    .context(QueryProfileSnafu { name: name.clone() })?;
+                Ok((name.clone(), settings))
+            })
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -63,7 +63,7 @@ impl Step for Get {
             .expect("Could not establish connection to Elasticsearch");
 
         // Build ES query
-        let dsl = build_features_query(&self.indexes, &self.id);
+        let dsl = build_features_query(&self.indexes, std::slice::from_ref(&self.id), false);
 
         // Fetch documents
         self.results = {